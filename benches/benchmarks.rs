@@ -1,5 +1,4 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
-use std::time::Duration;
 use link_shortener_backend::services::shortener::*;
 
 pub fn bench_generate_short_code(c: &mut Criterion) {
@@ -51,13 +50,14 @@ pub fn bench_generate_short_code_with_timestamp(c: &mut Criterion) {
 }
 
 pub fn bench_is_valid_custom_code(c: &mut Criterion) {
+    let long_code = "a".repeat(25);
     let test_codes = [
         "valid_code",
         "invalid code",
         "test123",
         "test-code_123",
         "",
-        "a".repeat(25).as_str(),
+        long_code.as_str(),
     ];
     
     c.bench_function("is_valid_custom_code", |b| {
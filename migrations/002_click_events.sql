@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS click_events (
+    id BIGSERIAL PRIMARY KEY,
+    short_code VARCHAR(10) NOT NULL REFERENCES links(short_code) ON DELETE CASCADE,
+    clicked_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+    referrer TEXT,
+    user_agent TEXT,
+    ip_hash VARCHAR(64),
+    country VARCHAR(2)
+);
+
+CREATE INDEX idx_click_events_code_time ON click_events(short_code, clicked_at);
@@ -0,0 +1,2 @@
+ALTER TABLE links ALTER COLUMN short_code TYPE VARCHAR(32);
+ALTER TABLE click_events ALTER COLUMN short_code TYPE VARCHAR(32);
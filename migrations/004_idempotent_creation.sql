@@ -0,0 +1,13 @@
+ALTER TABLE links
+    ADD COLUMN owner TEXT,
+    ADD COLUMN normalized_url TEXT,
+    ADD COLUMN is_custom BOOLEAN DEFAULT FALSE NOT NULL;
+
+CREATE INDEX idx_links_dedup ON links(normalized_url, owner) WHERE NOT is_custom AND expires_at IS NULL;
+
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    key VARCHAR(255) PRIMARY KEY,
+    fingerprint VARCHAR(64) NOT NULL,
+    short_code VARCHAR(32),
+    created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL
+);
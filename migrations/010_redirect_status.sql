@@ -0,0 +1,6 @@
+-- Links created before the status became configurable were all served with
+-- 301, so they keep it. New links always get an explicit status from the
+-- application (DEFAULT_REDIRECT_STATUS when the request names none).
+ALTER TABLE links ADD COLUMN redirect_status SMALLINT NOT NULL DEFAULT 301
+    CHECK (redirect_status IN (301, 302, 307, 308));
+ALTER TABLE links ALTER COLUMN redirect_status DROP DEFAULT;
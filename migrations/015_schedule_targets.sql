@@ -0,0 +1 @@
+ALTER TABLE links ADD COLUMN schedule_targets JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,40 @@
+CREATE TABLE IF NOT EXISTS links (
+    id BLOB PRIMARY KEY NOT NULL,
+    short_code TEXT UNIQUE NOT NULL,
+    original_url TEXT NOT NULL,
+    clicks INTEGER DEFAULT 0 NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT,
+    owner TEXT,
+    normalized_url TEXT,
+    is_custom BOOLEAN DEFAULT FALSE NOT NULL
+);
+
+CREATE INDEX idx_expires_at ON links(expires_at) WHERE expires_at IS NOT NULL;
+CREATE INDEX idx_links_dedup ON links(normalized_url, owner) WHERE NOT is_custom AND expires_at IS NULL;
+
+CREATE TABLE IF NOT EXISTS click_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    short_code TEXT NOT NULL REFERENCES links(short_code) ON DELETE CASCADE,
+    clicked_at TEXT NOT NULL,
+    referrer TEXT,
+    user_agent TEXT,
+    ip_hash TEXT,
+    country TEXT
+);
+
+CREATE INDEX idx_click_events_code_time ON click_events(short_code, clicked_at);
+
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    key TEXT PRIMARY KEY NOT NULL,
+    fingerprint TEXT NOT NULL,
+    short_code TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS link_code_seq (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    value INTEGER NOT NULL
+);
+
+INSERT INTO link_code_seq (id, value) VALUES (1, -1);
@@ -0,0 +1 @@
+ALTER TABLE links ADD COLUMN expired_redirect_url TEXT;
@@ -0,0 +1,5 @@
+-- Links created before the status became configurable were all served with
+-- 301, so they keep it. SQLite can't drop a column default; the application
+-- always writes the status explicitly.
+ALTER TABLE links ADD COLUMN redirect_status INTEGER NOT NULL DEFAULT 301
+    CHECK (redirect_status IN (301, 302, 307, 308));
@@ -0,0 +1 @@
+ALTER TABLE links ADD COLUMN geo_targets TEXT NOT NULL DEFAULT '[]';
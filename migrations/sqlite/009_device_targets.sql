@@ -0,0 +1,2 @@
+ALTER TABLE links ADD COLUMN device_targets TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE click_events ADD COLUMN matched_rule TEXT;
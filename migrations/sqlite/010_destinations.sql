@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS link_destinations (
+    link_id BLOB NOT NULL REFERENCES links(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    weight INTEGER NOT NULL CHECK (weight > 0),
+    PRIMARY KEY (link_id, position)
+);
+
+ALTER TABLE links ADD COLUMN sticky_variants BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE click_events ADD COLUMN variant INTEGER;
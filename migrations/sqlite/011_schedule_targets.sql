@@ -0,0 +1 @@
+ALTER TABLE links ADD COLUMN schedule_targets TEXT NOT NULL DEFAULT '[]';
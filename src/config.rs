@@ -1,25 +1,180 @@
+use ipnetwork::IpNetwork;
 use once_cell::sync::Lazy;
 use serde::Deserialize;
 
+use crate::models::link::RedirectStatus;
+use crate::services::{code_generator::{CodeStrategy, MIN_SECRET_LEN}, shortener};
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub database_url: String,
-    pub redis_url: String,
+    pub redis_url: Option<String>,
     pub server_host: String,
     pub server_port: u16,
     pub base_url: String,
+    pub ip_hash_salt: String,
+    /// Reverse proxies whose `X-Forwarded-For` is believed; empty trusts none.
+    pub trusted_proxies: Vec<IpNetwork>,
+    pub click_flush_interval_secs: u64,
+    pub click_flush_batch_size: usize,
+    pub l1_cache_capacity: u64,
+    pub l1_cache_ttl_secs: u64,
+    pub cache_early_refresh_secs: u64,
+    pub negative_cache_ttl_secs: u64,
+    pub short_code_length: usize,
+    pub short_code_alphabet: String,
+    pub short_code_max_attempts: u32,
+    pub short_code_strategy: CodeStrategy,
+    pub short_code_secret: String,
+    pub reserved_codes: Vec<String>,
+    pub blocked_words: Vec<String>,
+    pub deduplicate_urls: bool,
+    pub password_max_attempts: i64,
+    pub password_attempt_window_secs: u64,
+    pub pending_link_url: Option<String>,
+    pub expired_link_url: Option<String>,
+    pub default_redirect_status: RedirectStatus,
+    pub geoip_database: Option<String>,
+    pub variant_cookie_max_age_secs: u64,
+    pub allow_private_destinations: bool,
+    pub shortener_domains: Vec<String>,
+}
+
+impl Config {
+    pub fn early_refresh_window(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.cache_early_refresh_secs)
+    }
+
+    pub fn negative_cache_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.negative_cache_ttl_secs)
+    }
+
+    pub fn password_attempt_window(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.password_attempt_window_secs)
+    }
+
+    pub fn variant_cookie_max_age(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.variant_cookie_max_age_secs)
+    }
 }
 
 pub static CONFIG: Lazy<Config> = Lazy::new(|| {
     dotenv::dotenv().ok();
-    Config {
+    let config = Config {
         database_url: std::env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
-        redis_url: std::env::var("REDIS_URL").expect("REDIS_URL must be set"),
+        redis_url: std::env::var("REDIS_URL").ok().filter(|url| !url.is_empty()),
         server_host: std::env::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
         server_port: std::env::var("SERVER_PORT")
             .unwrap_or_else(|_| "8080".to_string())
             .parse()
             .expect("SERVER_PORT must be a number"),
         base_url: std::env::var("BASE_URL").unwrap_or_else(|_| "https://link.aescipher.xyz".to_string()),
-    }
-}); 
\ No newline at end of file
+        ip_hash_salt: std::env::var("IP_HASH_SALT")
+            .ok()
+            .filter(|salt| !salt.is_empty())
+            .unwrap_or_else(|| {
+                tracing::warn!("IP_HASH_SALT is not set; using a random salt, so unique visitors are counted afresh after every restart");
+                std::iter::repeat_with(fastrand::alphanumeric).take(32).collect()
+            }),
+        trusted_proxies: list_var("TRUSTED_PROXIES")
+            .iter()
+            .map(|entry| entry.parse())
+            .collect::<Result<_, _>>()
+            .expect("TRUSTED_PROXIES must be a comma-separated list of IP addresses or CIDR ranges"),
+        click_flush_interval_secs: std::env::var("CLICK_FLUSH_INTERVAL_SECS")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse()
+            .expect("CLICK_FLUSH_INTERVAL_SECS must be a number"),
+        click_flush_batch_size: std::env::var("CLICK_FLUSH_BATCH_SIZE")
+            .unwrap_or_else(|_| "500".to_string())
+            .parse()
+            .expect("CLICK_FLUSH_BATCH_SIZE must be a number"),
+        l1_cache_capacity: std::env::var("L1_CACHE_CAPACITY")
+            .unwrap_or_else(|_| "10000".to_string())
+            .parse()
+            .expect("L1_CACHE_CAPACITY must be a number"),
+        l1_cache_ttl_secs: std::env::var("L1_CACHE_TTL_SECS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .expect("L1_CACHE_TTL_SECS must be a number"),
+        cache_early_refresh_secs: std::env::var("CACHE_EARLY_REFRESH_SECS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .expect("CACHE_EARLY_REFRESH_SECS must be a number"),
+        negative_cache_ttl_secs: std::env::var("NEGATIVE_CACHE_TTL_SECS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .expect("NEGATIVE_CACHE_TTL_SECS must be a number"),
+        short_code_length: std::env::var("SHORT_CODE_LENGTH")
+            .unwrap_or_else(|_| "7".to_string())
+            .parse()
+            .ok()
+            .filter(|length| (4..=32).contains(length))
+            .expect("SHORT_CODE_LENGTH must be a number between 4 and 32"),
+        short_code_alphabet: std::env::var("SHORT_CODE_ALPHABET")
+            .ok()
+            .inspect(|alphabet| {
+                assert!(
+                    shortener::is_valid_alphabet(alphabet),
+                    "SHORT_CODE_ALPHABET must contain at least two distinct characters from [A-Za-z0-9_-]"
+                );
+            })
+            .unwrap_or_else(|| shortener::DEFAULT_ALPHABET.to_string()),
+        short_code_max_attempts: std::env::var("SHORT_CODE_MAX_ATTEMPTS")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse()
+            .expect("SHORT_CODE_MAX_ATTEMPTS must be a number"),
+        short_code_strategy: std::env::var("SHORT_CODE_STRATEGY")
+            .unwrap_or_else(|_| "random".to_string())
+            .parse()
+            .expect("SHORT_CODE_STRATEGY must be one of hash, random, sequential, timestamp"),
+        short_code_secret: std::env::var("SHORT_CODE_SECRET").unwrap_or_default(),
+        reserved_codes: list_var("RESERVED_CODES"),
+        blocked_words: list_var("BLOCKED_WORDS"),
+        deduplicate_urls: std::env::var("DEDUPLICATE_URLS")
+            .map(|value| matches!(value.as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false),
+        password_max_attempts: std::env::var("PASSWORD_MAX_ATTEMPTS")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse()
+            .expect("PASSWORD_MAX_ATTEMPTS must be a number"),
+        password_attempt_window_secs: std::env::var("PASSWORD_ATTEMPT_WINDOW_SECS")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse()
+            .expect("PASSWORD_ATTEMPT_WINDOW_SECS must be a number"),
+        pending_link_url: std::env::var("PENDING_LINK_URL").ok().filter(|url| !url.is_empty()),
+        expired_link_url: std::env::var("EXPIRED_LINK_URL").ok().filter(|url| !url.is_empty()),
+        default_redirect_status: std::env::var("DEFAULT_REDIRECT_STATUS")
+            .unwrap_or_else(|_| "302".to_string())
+            .parse()
+            .expect("DEFAULT_REDIRECT_STATUS must be one of 301, 302, 307, 308"),
+        geoip_database: std::env::var("GEOIP_DATABASE").ok().filter(|path| !path.is_empty()),
+        variant_cookie_max_age_secs: std::env::var("VARIANT_COOKIE_MAX_AGE_SECS")
+            .unwrap_or_else(|_| "2592000".to_string())
+            .parse()
+            .expect("VARIANT_COOKIE_MAX_AGE_SECS must be a number"),
+        allow_private_destinations: std::env::var("ALLOW_PRIVATE_DESTINATIONS")
+            .map(|value| matches!(value.as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false),
+        shortener_domains: list_var("SHORTENER_DOMAINS"),
+    };
+
+    assert!(
+        config.short_code_strategy != CodeStrategy::Sequential || config.short_code_secret.len() >= MIN_SECRET_LEN,
+        "SHORT_CODE_SECRET must be at least {} characters when SHORT_CODE_STRATEGY=sequential",
+        MIN_SECRET_LEN
+    );
+
+    config
+});
+
+/// Reads a comma-separated variable, ignoring blank entries.
+fn list_var(name: &str) -> Vec<String> {
+    std::env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(str::to_string)
+        .collect()
+}
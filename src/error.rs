@@ -17,12 +17,42 @@ pub enum AppError {
     #[error("Invalid URL: {0}")]
     InvalidUrl(String),
     
+    #[error("Destination points to a private or reserved address")]
+    PrivateDestination,
+    
+    #[error("Destination points back at this shortener")]
+    RedirectLoop,
+    
+    #[error("Destination goes through another link shortener")]
+    NestedShortener,
+    
     #[error("Short code not found")]
     NotFound,
     
     #[error("Short code already exists")]
     Conflict,
     
+    #[error("Link has reached its click limit")]
+    Exhausted,
+    
+    #[error("Link is not active yet")]
+    NotYetActive,
+    
+    #[error("Link has expired")]
+    Expired,
+    
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    
+    #[error("Idempotency key reused with a different request")]
+    IdempotencyKeyReused,
+    
+    #[error("Request with this idempotency key is still in progress")]
+    IdempotencyKeyInProgress,
+    
+    #[error("Too many attempts")]
+    TooManyAttempts,
+    
     #[error("Internal server error")]
     Internal,
     
@@ -36,8 +66,18 @@ impl IntoResponse for AppError {
             AppError::Database(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error"),
             AppError::Redis(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Cache error"),
             AppError::InvalidUrl(_) => (StatusCode::BAD_REQUEST, "Invalid URL"),
+            AppError::PrivateDestination => (StatusCode::BAD_REQUEST, "Destination points to a private or reserved address"),
+            AppError::RedirectLoop => (StatusCode::BAD_REQUEST, "Destination points back at this shortener"),
+            AppError::NestedShortener => (StatusCode::BAD_REQUEST, "Destination goes through another link shortener"),
             AppError::NotFound => (StatusCode::NOT_FOUND, "Short code not found"),
             AppError::Conflict => (StatusCode::CONFLICT, "Short code already exists"),
+            AppError::Exhausted => (StatusCode::GONE, "Link has reached its click limit"),
+            AppError::NotYetActive => (StatusCode::NOT_FOUND, "Link is not active yet"),
+            AppError::Expired => (StatusCode::GONE, "Link has expired"),
+            AppError::InvalidRequest(ref message) => (StatusCode::BAD_REQUEST, message.as_str()),
+            AppError::IdempotencyKeyReused => (StatusCode::UNPROCESSABLE_ENTITY, "Idempotency-Key was already used for a different request"),
+            AppError::IdempotencyKeyInProgress => (StatusCode::CONFLICT, "A request with this Idempotency-Key is still being processed"),
+            AppError::TooManyAttempts => (StatusCode::TOO_MANY_REQUESTS, "Too many attempts, try again later"),
             AppError::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
             AppError::InternalServerError => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
         };
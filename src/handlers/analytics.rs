@@ -0,0 +1,124 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+};
+use sqlx::types::time::OffsetDateTime;
+use std::collections::HashMap;
+use time::Date;
+
+use crate::{
+    AppState,
+    error::AppError,
+    models::analytics::{AnalyticsQuery, AnalyticsResponse, GlobalAnalyticsResponse, TopLink, VariantClicks},
+    services::leaderboard::{self, MAX_WINDOW_DAYS},
+};
+
+const DEFAULT_WINDOW_DAYS: i64 = 30;
+const DEFAULT_TOP_LINKS: i64 = 10;
+const MAX_TOP_LINKS: i64 = 100;
+
+pub async fn link_analytics(
+    Path(short_code): Path<String>,
+    State(app_state): State<AppState>,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<Json<AnalyticsResponse>, AppError> {
+    let link = app_state
+        .db
+        .find_link_by_code(&short_code)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let since = window_start(window_days(&query));
+    let mut analytics = app_state.db.get_analytics(Some(&short_code), since).await?;
+
+    if !link.destinations.is_empty() {
+        let counts: HashMap<i64, i64> = app_state.db.get_variant_clicks(&short_code, since).await?.into_iter().collect();
+        analytics.clicks_by_variant = link
+            .destinations
+            .0
+            .into_iter()
+            .enumerate()
+            .map(|(variant, destination)| VariantClicks {
+                variant: variant as i64,
+                url: destination.url,
+                weight: destination.weight,
+                clicks: counts.get(&(variant as i64)).copied().unwrap_or(0),
+            })
+            .collect();
+    }
+
+    Ok(Json(analytics))
+}
+
+pub async fn global_analytics(
+    State(app_state): State<AppState>,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<Json<GlobalAnalyticsResponse>, AppError> {
+    let days = window_days(&query);
+    let limit = query.limit.unwrap_or(DEFAULT_TOP_LINKS).clamp(1, MAX_TOP_LINKS);
+    let since = window_start(days);
+
+    let mut analytics = app_state.db.get_analytics(None, since).await?;
+    analytics.top_links = top_links(&app_state, days, limit, since).await?;
+
+    let total_links = app_state.db.count_links().await?;
+
+    Ok(Json(GlobalAnalyticsResponse {
+        total_links,
+        analytics,
+    }))
+}
+
+/// Reads the leaderboard from Redis, falling back to aggregating click events
+/// in Postgres when the sorted sets are unavailable or empty.
+async fn top_links(
+    app_state: &AppState,
+    days: i64,
+    limit: i64,
+    since: OffsetDateTime,
+) -> Result<Vec<TopLink>, AppError> {
+    let ranked = leaderboard::top_links(app_state.cache.as_ref(), today(), days, limit as usize).await;
+
+    match ranked {
+        Ok(ranked) if !ranked.is_empty() => {
+            let codes: Vec<String> = ranked.iter().map(|(code, _)| code.clone()).collect();
+            let mut urls: HashMap<String, String> = app_state
+                .db
+                .get_links_by_codes(&codes)
+                .await?
+                .into_iter()
+                .map(|link| (link.short_code, link.original_url))
+                .collect();
+
+            Ok(ranked
+                .into_iter()
+                .filter_map(|(short_code, clicks)| {
+                    let original_url = urls.remove(&short_code)?;
+                    Some(TopLink { short_code, original_url, clicks })
+                })
+                .collect())
+        }
+        Ok(_) => app_state.db.top_links_since(since, limit).await,
+        Err(e) => {
+            tracing::warn!("Failed to read leaderboard from cache: {}", e);
+            app_state.db.top_links_since(since, limit).await
+        }
+    }
+}
+
+fn window_days(query: &AnalyticsQuery) -> i64 {
+    query.days.unwrap_or(DEFAULT_WINDOW_DAYS).clamp(1, MAX_WINDOW_DAYS)
+}
+
+/// Midnight UTC at the start of a window of `days` calendar days ending today,
+/// matching the per-day leaderboard buckets.
+fn window_start(days: i64) -> OffsetDateTime {
+    let first_day = today()
+        .checked_sub(time::Duration::days(days - 1))
+        .unwrap_or(Date::MIN);
+    first_day.midnight().assume_utc()
+}
+
+fn today() -> Date {
+    OffsetDateTime::now_utc().date()
+}
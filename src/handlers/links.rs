@@ -0,0 +1,111 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+
+use sqlx::types::time::OffsetDateTime;
+
+use crate::{
+    AppState,
+    config::CONFIG,
+    error::AppError,
+    handlers::shorten::{expiry_from_hours, validate_schedule, validate_url},
+    models::link::{Link, LinkResponse, ListLinksQuery, UpdateLinkRequest},
+    services::click_counter,
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+
+pub async fn list_links(
+    State(app_state): State<AppState>,
+    Query(query): Query<ListLinksQuery>,
+) -> Result<Json<Vec<LinkResponse>>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let links = app_state.db.list_links(limit, offset).await?;
+
+    Ok(Json(links.into_iter().map(link_response).collect()))
+}
+
+pub async fn get_link(
+    Path(short_code): Path<String>,
+    State(app_state): State<AppState>,
+) -> Result<Json<LinkResponse>, AppError> {
+    app_state
+        .db
+        .find_link_by_code(&short_code)
+        .await?
+        .map(|link| Json(link_response(link)))
+        .ok_or(AppError::NotFound)
+}
+
+pub async fn update_link(
+    Path(short_code): Path<String>,
+    State(app_state): State<AppState>,
+    Json(request): Json<UpdateLinkRequest>,
+) -> Result<Json<LinkResponse>, AppError> {
+    let existing = app_state
+        .db
+        .find_link_by_code(&short_code)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let original_url = match request.url {
+        Some(url) => {
+            validate_url(&url, &app_state.url_policy)?;
+            url
+        }
+        None => existing.original_url,
+    };
+
+    let expires_at = match (request.expires_in_hours, request.expires_at) {
+        (Some(_), Some(_)) => {
+            return Err(AppError::InvalidRequest("Use either expires_in_hours or expires_at, not both".to_string()));
+        }
+        (Some(hours), None) => hours.map(expiry_from_hours),
+        (None, Some(expires_at)) => expires_at,
+        (None, None) => existing.expires_at,
+    };
+    // Only a new expiry is checked, so links that already lapsed can still be edited.
+    if expires_at != existing.expires_at {
+        validate_schedule(existing.activates_at, expires_at, OffsetDateTime::now_utc())?;
+    }
+
+    let link = app_state
+        .db
+        .update_link(&short_code, &original_url, expires_at)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    invalidate_cached_link(&app_state, &short_code).await;
+
+    Ok(Json(link_response(link)))
+}
+
+pub async fn delete_link(
+    Path(short_code): Path<String>,
+    State(app_state): State<AppState>,
+) -> Result<StatusCode, AppError> {
+    if !app_state.db.delete_link(&short_code).await? {
+        return Err(AppError::NotFound);
+    }
+
+    invalidate_cached_link(&app_state, &short_code).await;
+    // A later link reusing this code must not inherit the old click limit.
+    let _ = app_state.cache.delete(&click_counter::remaining_key(&short_code)).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn invalidate_cached_link(app_state: &AppState, short_code: &str) {
+    if let Err(e) = app_state.cache.delete(short_code).await {
+        tracing::warn!("Failed to invalidate cached link {}: {}", short_code, e);
+    }
+}
+
+fn link_response(link: Link) -> LinkResponse {
+    LinkResponse::new(link, &CONFIG.base_url)
+}
@@ -1,3 +1,5 @@
 pub mod redirect;
 pub mod shorten;
-pub mod health; 
\ No newline at end of file
+pub mod health;
+pub mod links;
+pub mod analytics;
\ No newline at end of file
@@ -1,62 +1,547 @@
 use axum::{
-    extract::{Path, State},
+    extract::{ConnectInfo, Form, Path, RawQuery, State},
     response::{IntoResponse, Response},
-    http::StatusCode,
+    http::{header::{CACHE_CONTROL, SET_COOKIE, USER_AGENT}, HeaderMap, HeaderValue, StatusCode},
 };
+use once_cell::sync::Lazy;
+use prometheus::{register_counter, register_int_counter_vec, Counter, IntCounterVec};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::time::SystemTime;
+use time::OffsetDateTime;
 use crate::{
+    config::CONFIG,
     error::AppError,
+    models::link::{CachedLink, Link, LinkStatus},
+    services::{
+        analytics::{click_event_from_request, client_ip},
+        cache::{should_refresh_early, CacheEntry, DEFAULT_TTL}, click_counter, destination, leaderboard, password,
+        rotation,
+        targeting::{self, Choice, MatchedRule, Visitor},
+        user_agent,
+    },
     AppState,
 };
 
+static COALESCED_LOOKUPS: Lazy<Counter> = Lazy::new(|| {
+    register_counter!("redirect_lookups_coalesced_total", "Redirect misses served by another request's lookup")
+        .expect("Failed to register prometheus counter")
+});
+
+static EARLY_REFRESHES: Lazy<Counter> = Lazy::new(|| {
+    register_counter!("cache_early_refresh_total", "Cache entries refreshed ahead of expiry")
+        .expect("Failed to register prometheus counter")
+});
+
+static EXHAUSTED_REDIRECTS: Lazy<Counter> = Lazy::new(|| {
+    register_counter!("redirect_exhausted_total", "Redirects refused because the link reached its click limit")
+        .expect("Failed to register prometheus counter")
+});
+
+static NOT_FOUND_REDIRECTS: Lazy<Counter> = Lazy::new(|| {
+    register_counter!("redirect_not_found_total", "Redirects requested for codes that don't exist")
+        .expect("Failed to register prometheus counter")
+});
+
+static EXPIRED_REDIRECTS: Lazy<Counter> = Lazy::new(|| {
+    register_counter!("redirect_expired_total", "Redirects requested after the link expired")
+        .expect("Failed to register prometheus counter")
+});
+
+static PENDING_REDIRECTS: Lazy<Counter> = Lazy::new(|| {
+    register_counter!("redirect_pending_total", "Redirects requested before the link's activation time")
+        .expect("Failed to register prometheus counter")
+});
+
+static PASSWORD_ATTEMPTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("link_password_attempts_total", "Password attempts on protected links by outcome", &["outcome"])
+        .expect("Failed to register prometheus counter")
+});
+
+#[derive(Debug, Deserialize)]
+pub struct UnlockForm {
+    pub password: String,
+}
+
 pub async fn redirect(
     Path(short_code): Path<String>,
+    RawQuery(query): RawQuery,
+    State(app_state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    serve_redirect(&app_state, &short_code, None, query.as_deref(), &headers, peer).await
+}
+
+/// `/{code}/{*path}`: the same redirect with a path suffix, which only links
+/// that forward paths accept.
+pub async fn redirect_with_path(
+    Path((short_code, suffix)): Path<(String, String)>,
+    RawQuery(query): RawQuery,
     State(app_state): State<AppState>,
-) -> Result<impl IntoResponse, AppError> {
-    let mut cache = app_state.cache.lock().await;
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    serve_redirect(&app_state, &short_code, Some(&suffix), query.as_deref(), &headers, peer).await
+}
+
+async fn serve_redirect(
+    app_state: &AppState,
+    short_code: &str,
+    suffix: Option<&str>,
+    query: Option<&str>,
+    headers: &HeaderMap,
+    peer: SocketAddr,
+) -> Result<Response, AppError> {
+    let cache = app_state.cache.as_ref();
+    
+    let cached = cache.lookup(short_code).await.ok().flatten();
+    
+    if let Some(CacheEntry::Absent) = cached {
+        NOT_FOUND_REDIRECTS.inc();
+        return Err(AppError::NotFound);
+    }
+    
+    let hit = match cached {
+        Some(CacheEntry::Present { value, ttl }) => CachedLink::decode(&value).map(|link| (link, ttl)),
+        _ => None,
+    };
     
-    if let Ok(Some(url)) = cache.get(&short_code).await {
-        let click_key = format!("clicks:{}", short_code);
-        let _ = cache.incr(&click_key).await;
+    if let Some((cached_link, ttl)) = hit {
+        if let Some(response) = inactive_response(&cached_link) {
+            return response;
+        }
         
-        let etag = format!("\"{}\"", &short_code);
+        if ttl.is_some_and(|ttl| should_refresh_early(ttl, CONFIG.early_refresh_window(), fastrand::f64())) {
+            EARLY_REFRESHES.inc();
+            let app_state = app_state.clone();
+            let code = short_code.to_string();
+            tokio::spawn(async move {
+                if let Err(e) = load_link(&app_state, &code).await {
+                    tracing::warn!("Early refresh of {} failed: {}", code, e);
+                }
+            });
+        }
         
-        let response = Response::builder()
-            .status(StatusCode::MOVED_PERMANENTLY)
-            .header("location", &url)
-            .header("etag", &etag)
-            .header("cache-control", "public, max-age=31536000, immutable")
-            .header("x-cache", "HIT")
-            .body(axum::body::Body::empty())
-            .map_err(|_| AppError::InternalServerError)?;
-            
+        if cached_link.protected {
+            return password_form(None, StatusCode::OK);
+        }
+        
+        let visitor = visitor(app_state, short_code, headers, peer);
+        let choice = targeting::choose(&cached_link, &visitor, OffsetDateTime::now_utc(), fastrand::f64());
+        let location = destination::resolve(&cached_link, choice.url, suffix, query)?;
+        
+        track_click(app_state, short_code, cached_link.max_clicks, headers, peer, &visitor, &choice).await?;
+        
+        let mut response = redirect_response(short_code, &cached_link, &location, choice.rule, "HIT")?;
+        remember_variant(&mut response, short_code, &cached_link, &visitor, &choice);
         return Ok(response);
     }
     
-    drop(cache);
-
-    if let Some(link) = app_state.db.get_link_by_code(&short_code).await? {
-        let mut cache = app_state.cache.lock().await;
-        let _ = cache.set_with_default_ttl(&short_code, &link.original_url).await;
+    if let Some(link) = load_link(app_state, short_code).await? {
+        let cached_link = CachedLink::from(&link);
+        if let Some(response) = inactive_response(&cached_link) {
+            return response;
+        }
         
-        let db_service = app_state.db.clone();
-        let code_for_bg = short_code.clone();
-        tokio::spawn(async move {
-            let _ = db_service.increment_clicks(&code_for_bg).await;
-        });
+        if link.password_hash.is_some() {
+            return password_form(None, StatusCode::OK);
+        }
         
-        let etag = format!("\"{}\"", &short_code);
+        let visitor = visitor(app_state, short_code, headers, peer);
+        let choice = targeting::choose(&cached_link, &visitor, OffsetDateTime::now_utc(), fastrand::f64());
+        let location = destination::resolve(&cached_link, choice.url, suffix, query)?;
         
-        let response = Response::builder()
-            .status(StatusCode::MOVED_PERMANENTLY)
-            .header("location", &link.original_url)
-            .header("etag", &etag)
-            .header("cache-control", "public, max-age=31536000, immutable")
-            .header("x-cache", "MISS")
-            .body(axum::body::Body::empty())
-            .map_err(|_| AppError::InternalServerError)?;
-            
+        track_click(app_state, short_code, link.max_clicks, headers, peer, &visitor, &choice).await?;
+        
+        let mut response = redirect_response(short_code, &cached_link, &location, choice.rule, "MISS")?;
+        remember_variant(&mut response, short_code, &cached_link, &visitor, &choice);
         Ok(response)
     } else {
+        NOT_FOUND_REDIRECTS.inc();
         Err(AppError::NotFound)
     }
-} 
\ No newline at end of file
+}
+
+pub async fn unlock(
+    Path(short_code): Path<String>,
+    RawQuery(query): RawQuery,
+    State(app_state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<UnlockForm>,
+) -> Result<Response, AppError> {
+    serve_unlock(&app_state, &short_code, None, query.as_deref(), &headers, peer, form).await
+}
+
+pub async fn unlock_with_path(
+    Path((short_code, suffix)): Path<(String, String)>,
+    RawQuery(query): RawQuery,
+    State(app_state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<UnlockForm>,
+) -> Result<Response, AppError> {
+    serve_unlock(&app_state, &short_code, Some(&suffix), query.as_deref(), &headers, peer, form).await
+}
+
+/// Checks the password for a protected link and redirects on success. Attempts
+/// are counted per code in fixed windows before verifying, and given back when
+/// the password was right; once a code reaches the limit, further attempts are
+/// refused until the window rolls over.
+async fn serve_unlock(
+    app_state: &AppState,
+    short_code: &str,
+    suffix: Option<&str>,
+    query: Option<&str>,
+    headers: &HeaderMap,
+    peer: SocketAddr,
+    form: UnlockForm,
+) -> Result<Response, AppError> {
+    let link = app_state
+        .db
+        .find_link_by_code(short_code)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    
+    let cached_link = CachedLink::from(&link);
+    if let Some(response) = inactive_response(&cached_link) {
+        return response;
+    }
+    
+    if let Some(password_hash) = link.password_hash.clone() {
+        let window = CONFIG.password_attempt_window();
+        let key = password::attempts_key(short_code, window, SystemTime::now());
+        
+        if !password::claim_attempt(app_state.cache.as_ref(), &key, CONFIG.password_max_attempts, window).await {
+            PASSWORD_ATTEMPTS.with_label_values(&["throttled"]).inc();
+            return Err(AppError::TooManyAttempts);
+        }
+        
+        let verified = tokio::task::spawn_blocking(move || password::verify_password(&password_hash, &form.password))
+            .await
+            .map_err(|_| AppError::Internal)?;
+        
+        if !verified {
+            PASSWORD_ATTEMPTS.with_label_values(&["rejected"]).inc();
+            return password_form(Some("Incorrect password"), StatusCode::UNAUTHORIZED);
+        }
+        PASSWORD_ATTEMPTS.with_label_values(&["accepted"]).inc();
+        password::release_attempt(app_state.cache.as_ref(), &key, window).await;
+    }
+    
+    let visitor = visitor(app_state, short_code, headers, peer);
+    let choice = targeting::choose(&cached_link, &visitor, OffsetDateTime::now_utc(), fastrand::f64());
+    let location = destination::resolve(&cached_link, choice.url, suffix, query)?;
+    
+    track_click(app_state, short_code, link.max_clicks, headers, peer, &visitor, &choice).await?;
+    
+    let mut response = Response::builder()
+        .status(StatusCode::SEE_OTHER)
+        .header("location", location)
+        .header("cache-control", "no-store")
+        .header("x-link-rule", choice.rule.to_string())
+        .body(axum::body::Body::empty())
+        .map_err(|_| AppError::InternalServerError)?;
+    remember_variant(&mut response, short_code, &cached_link, &visitor, &choice);
+    Ok(response)
+}
+
+/// Redirects with the link's chosen status. Permanent redirects may be cached
+/// for good; temporary ones must not be stored, so every visit reaches us and
+/// destination edits apply immediately. Click-limited, geo-targeted, A/B and
+/// scheduled links always use the temporary counterpart, since a cached
+/// redirect would bypass the limit, serve one country's destination to all,
+/// pin the variant or outlive its window. `X-Link-Rule` names the targeting
+/// rule that picked the destination.
+fn redirect_response(
+    short_code: &str,
+    link: &CachedLink,
+    location: &str,
+    rule: MatchedRule,
+    cache_status: &'static str,
+) -> Result<Response, AppError> {
+    let redirect_status = if link.max_clicks.is_some()
+        || !link.geo_targets.is_empty()
+        || !link.destinations.is_empty()
+        || !link.schedule_targets.is_empty()
+    {
+        link.redirect_status.temporary()
+    } else {
+        link.redirect_status
+    };
+    let status = StatusCode::from_u16(redirect_status.into()).map_err(|_| AppError::InternalServerError)?;
+    
+    let builder = if redirect_status.is_permanent() {
+        let builder = Response::builder()
+            .status(status)
+            .header("etag", format!("\"{}\"", short_code))
+            .header("cache-control", "public, max-age=31536000, immutable");
+        // Shared caches must not hand one device's destination to another.
+        if link.device_targets.is_empty() {
+            builder
+        } else {
+            builder.header("vary", "user-agent")
+        }
+    } else {
+        Response::builder()
+            .status(status)
+            .header("cache-control", "no-store")
+    };
+    
+    builder
+        .header("location", location)
+        .header("x-cache", cache_status)
+        .header("x-link-rule", rule.to_string())
+        .body(axum::body::Body::empty())
+        .map_err(|_| AppError::InternalServerError)
+}
+
+/// Answer for a link that exists but isn't redirecting right now, or `None`
+/// while it is active. Links that haven't launched fall back to
+/// `PENDING_LINK_URL`; expired ones to their own `expired_redirect_url`, then
+/// `EXPIRED_LINK_URL`. Without a fallback the visitor gets a 404 or 410.
+fn inactive_response(link: &CachedLink) -> Option<Result<Response, AppError>> {
+    match link.status(OffsetDateTime::now_utc()) {
+        LinkStatus::Active => None,
+        LinkStatus::Pending => {
+            PENDING_REDIRECTS.inc();
+            Some(fallback_response(CONFIG.pending_link_url.as_deref(), AppError::NotYetActive))
+        }
+        LinkStatus::Expired => {
+            EXPIRED_REDIRECTS.inc();
+            let fallback = link.expired_redirect_url.as_deref().or(CONFIG.expired_link_url.as_deref());
+            Some(fallback_response(fallback, AppError::Expired))
+        }
+    }
+}
+
+/// Temporary redirect to `fallback`, or `error` when there is none. Neither
+/// may be cached: the link's schedule can still be edited, and a pending link
+/// must start working the moment it launches.
+fn fallback_response(fallback: Option<&str>, error: AppError) -> Result<Response, AppError> {
+    let mut response = match fallback {
+        Some(url) => Response::builder()
+            .status(StatusCode::FOUND)
+            .header("location", url)
+            .body(axum::body::Body::empty())
+            .map_err(|_| AppError::InternalServerError)?,
+        None => error.into_response(),
+    };
+    response.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    
+    Ok(response)
+}
+
+/// Small standalone page asking for the password of a protected link. Never
+/// cached, so entering the password always reaches the server. The form posts
+/// back to the URL it was served from, keeping any forwarded path and query.
+fn password_form(error: Option<&str>, status: StatusCode) -> Result<Response, AppError> {
+    let error = error
+        .map(|message| format!("<p class=\"error\">{}</p>", escape_html(message)))
+        .unwrap_or_default();
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>Password required</title>
+<style>
+body {{ font-family: system-ui, sans-serif; display: flex; justify-content: center; padding-top: 15vh; }}
+form {{ display: flex; flex-direction: column; gap: 0.75rem; width: 18rem; }}
+.error {{ color: #b00020; margin: 0; }}
+</style>
+</head>
+<body>
+<form method="post">
+<h1>Password required</h1>
+{error}
+<input type="password" name="password" autocomplete="current-password" required autofocus>
+<button type="submit">Continue</button>
+</form>
+</body>
+</html>
+"#,
+        error = error,
+    );
+    
+    Response::builder()
+        .status(status)
+        .header("content-type", "text/html; charset=utf-8")
+        .header("cache-control", "no-store")
+        .body(axum::body::Body::from(body))
+        .map_err(|_| AppError::InternalServerError)
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Loads a link from Postgres and refills the cache. Concurrent misses for the
+/// same code share one lookup, so an expiring hot key costs a single query.
+/// Expired links are returned too, and cached as briefly as a missing code, so
+/// the caller can answer 410 (or redirect to the fallback) instead of 404.
+async fn load_link(app_state: &AppState, short_code: &str) -> Result<Option<Link>, AppError> {
+    let mut leader = false;
+    let link = app_state
+        .link_lookups
+        .run(short_code, || {
+            leader = true;
+            async {
+                let link = app_state.db.find_link_by_code(short_code).await?;
+                let now = OffsetDateTime::now_utc();
+                match &link {
+                    Some(link) if link.expires_at.is_some_and(|expires_at| expires_at <= now) => {
+                        let cached = CachedLink::from(link).encode();
+                        let _ = app_state.cache.set(short_code, &cached, CONFIG.negative_cache_ttl()).await;
+                    }
+                    Some(link) => {
+                        let _ = app_state.cache.set_link(link, DEFAULT_TTL).await;
+                    }
+                    None => {
+                        let _ = app_state.cache.set_negative(short_code, CONFIG.negative_cache_ttl()).await;
+                    }
+                }
+                Ok::<_, AppError>(link)
+            }
+        })
+        .await?;
+
+    if !leader {
+        COALESCED_LOOKUPS.inc();
+    }
+
+    Ok(link)
+}
+
+/// What targeting rules and analytics may know about the visitor.
+fn visitor(app_state: &AppState, short_code: &str, headers: &HeaderMap, peer: SocketAddr) -> Visitor {
+    let (device, platform) = headers
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map_or((None, None), user_agent::classify);
+    
+    Visitor {
+        country: app_state
+            .geoip
+            .as_ref()
+            .and_then(|geoip| geoip.country(client_ip(headers, peer, &CONFIG.trusted_proxies))),
+        device,
+        platform,
+        variant: rotation::assigned_variant(headers, short_code),
+    }
+}
+
+/// Pins the A/B variant just shown with a cookie, for sticky links whose
+/// visitor wasn't already on it.
+fn remember_variant(response: &mut Response, short_code: &str, link: &CachedLink, visitor: &Visitor, choice: &Choice) {
+    let Some(variant) = choice.variant.filter(|&variant| link.sticky_variants && visitor.variant != Some(variant)) else {
+        return;
+    };
+    
+    let secure = CONFIG.base_url.starts_with("https://");
+    let cookie = rotation::variant_cookie(short_code, variant, CONFIG.variant_cookie_max_age(), secure);
+    if let Ok(value) = HeaderValue::from_str(&cookie) {
+        response.headers_mut().append(SET_COOKIE, value);
+    }
+}
+
+/// Counts a redirect towards the link's totals, the leaderboard and its click
+/// events. Fails with `Exhausted` when a click-limited link has no clicks left.
+async fn track_click(
+    app_state: &AppState,
+    short_code: &str,
+    max_clicks: Option<i64>,
+    headers: &HeaderMap,
+    peer: SocketAddr,
+    visitor: &Visitor,
+    choice: &Choice<'_>,
+) -> Result<(), AppError> {
+    match max_clicks {
+        Some(max_clicks) => {
+            if !click_counter::claim_limited_click(app_state.cache.as_ref(), app_state.db.as_ref(), short_code, max_clicks).await? {
+                EXHAUSTED_REDIRECTS.inc();
+                return Err(AppError::Exhausted);
+            }
+        }
+        None => count_click(app_state, short_code).await,
+    }
+    let _ = leaderboard::record_click(app_state.cache.as_ref(), short_code, OffsetDateTime::now_utc().date()).await;
+    
+    record_click(app_state, short_code, headers, peer, visitor.country.clone(), choice);
+    Ok(())
+}
+
+/// Buffers the click in the cache, or writes it straight to the database when
+/// the cache is unavailable so an outage doesn't lose clicks.
+async fn count_click(app_state: &AppState, short_code: &str) {
+    if click_counter::record_click(app_state.cache.as_ref(), short_code).await.is_ok() {
+        return;
+    }
+
+    if let Err(e) = app_state.db.add_clicks(&[short_code.to_string()], &[1]).await {
+        tracing::warn!("Failed to count click for {}: {}", short_code, e);
+    }
+}
+
+fn record_click(
+    app_state: &AppState,
+    short_code: &str,
+    headers: &HeaderMap,
+    peer: SocketAddr,
+    country: Option<String>,
+    choice: &Choice,
+) {
+    let variant = choice.variant.map(|variant| variant as i16);
+    let event = click_event_from_request(short_code, headers, peer, country, choice.rule.to_string(), variant);
+    let db_service = app_state.db.clone();
+    tokio::spawn(async move {
+        if let Err(e) = db_service.record_click(&event).await {
+            tracing::warn!("Failed to record click for {}: {}", event.short_code, e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::link::{GeoTarget, RedirectStatus};
+
+    #[test]
+    fn test_geo_targeted_link_is_never_cached_permanently() {
+        let link = CachedLink {
+            redirect_status: RedirectStatus::MovedPermanently,
+            geo_targets: vec![GeoTarget {
+                countries: vec!["DE".to_string()],
+                url: "https://example.de".to_string(),
+            }],
+            ..CachedLink::for_url("https://example.com")
+        };
+
+        let response = redirect_response("geo", &link, "https://example.de", MatchedRule::Geo(0), "miss").unwrap();
+
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+        assert!(!response.headers().contains_key("etag"));
+    }
+
+    #[test]
+    fn test_plain_link_keeps_permanent_status() {
+        let link = CachedLink {
+            redirect_status: RedirectStatus::MovedPermanently,
+            ..CachedLink::for_url("https://example.com")
+        };
+
+        let response = redirect_response("perm", &link, "https://example.com", MatchedRule::Default, "miss").unwrap();
+
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.headers()[CACHE_CONTROL], "public, max-age=31536000, immutable");
+    }
+}
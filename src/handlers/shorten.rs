@@ -1,11 +1,12 @@
 use axum::{
     extract::State,
-    response::Json,
+    http::{HeaderMap, HeaderValue},
+    response::{IntoResponse, Json, Response},
 };
 use once_cell::sync::Lazy;
-use prometheus::Counter;
+use prometheus::{register_int_counter_vec, Counter, IntCounterVec};
+use sha2::{Digest, Sha256};
 use sqlx::types::time::OffsetDateTime;
-use time::Duration;
 use url::Url;
 use uuid::Uuid;
 
@@ -13,8 +14,17 @@ use crate::{
     AppState,
     config::CONFIG,
     error::AppError,
-    models::link::{CreateLinkRequest, CreateLinkResponse, Link},
-    services::shortener::{generate_short_code_base62, is_valid_custom_code},
+    models::link::{CreateLinkRequest, CreateLinkResponse, IdempotencyRecord, Link},
+    services::{
+        code_generator::CodeRequest,
+        code_policy::CodeRejection,
+        password,
+        rotation,
+        schedule,
+        shortener::is_valid_custom_code,
+        targeting,
+        url_policy::{UrlPolicy, UrlRejection},
+    },
 };
 
 static LINK_CREATION_COUNT: Lazy<Counter> = Lazy::new(|| {
@@ -22,64 +32,336 @@ static LINK_CREATION_COUNT: Lazy<Counter> = Lazy::new(|| {
         .expect("Failed to register prometheus counter")
 });
 
+static LINK_DEDUPLICATION_COUNT: Lazy<Counter> = Lazy::new(|| {
+    prometheus::register_counter!("link_deduplicated_total", "Create requests answered with an existing link")
+        .expect("Failed to register prometheus counter")
+});
+
+static SHORT_CODE_COLLISIONS: Lazy<Counter> = Lazy::new(|| {
+    prometheus::register_counter!("short_code_collisions_total", "Generated short codes that were already taken")
+        .expect("Failed to register prometheus counter")
+});
+
+static SHORT_CODE_FILTERED: Lazy<Counter> = Lazy::new(|| {
+    prometheus::register_counter!("short_code_filtered_total", "Generated short codes discarded as reserved or blocked")
+        .expect("Failed to register prometheus counter")
+});
+
+static DESTINATION_REJECTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("destination_rejections_total", "Destination URLs rejected by reason", &["reason"])
+        .expect("Failed to register prometheus counter")
+});
+
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
 pub async fn create_link(
     State(app_state): State<AppState>,
+    headers: HeaderMap,
     Json(request): Json<CreateLinkRequest>,
-) -> Result<Json<CreateLinkResponse>, AppError> {
-    let parsed_url = Url::parse(&request.url)
-        .map_err(|_| AppError::InvalidUrl("Invalid URL format".to_string()))?;
+) -> Result<Response, AppError> {
+    validate_url(&request.url, &app_state.url_policy)?;
     
-    if !matches!(parsed_url.scheme(), "http" | "https") {
-        return Err(AppError::InvalidUrl("Only HTTP and HTTPS URLs are allowed".to_string()));
+    let Some(idempotency_key) = idempotency_key(&headers)? else {
+        return Ok(Json(create_or_reuse_link(&app_state, request).await?).into_response());
+    };
+    
+    let fingerprint = request_fingerprint(&request);
+    match app_state.db.claim_idempotency_key(&idempotency_key, &fingerprint).await? {
+        None => {}
+        Some(record) if record.fingerprint != fingerprint => return Err(AppError::IdempotencyKeyReused),
+        Some(IdempotencyRecord { short_code: Some(short_code), .. }) => {
+            let mut response = Json(link_response(short_code)).into_response();
+            response.headers_mut().insert("idempotent-replayed", HeaderValue::from_static("true"));
+            return Ok(response);
+        }
+        Some(_) => return Err(AppError::IdempotencyKeyInProgress),
     }
     
-    let short_code = if let Some(custom_code) = request.custom_code {
-        if !is_valid_custom_code(&custom_code) {
-            return Err(AppError::InvalidUrl("Invalid custom code format".to_string()));
+    match create_or_reuse_link(&app_state, request).await {
+        Ok(response) => {
+            complete_idempotency_key(&app_state, &idempotency_key, &response.short_code).await;
+            Ok(Json(response).into_response())
         }
-        
-        if app_state.db.get_link_by_code(&custom_code).await?.is_some() {
-            return Err(AppError::Conflict);
+        Err(e) => {
+            if let Err(release_error) = app_state.db.release_idempotency_key(&idempotency_key).await {
+                tracing::warn!("Failed to release idempotency key: {}", release_error);
+            }
+            Err(e)
         }
-        
-        custom_code
-    } else {
-        generate_short_code_base62(&request.url)
+    }
+}
+
+/// Records `short_code` against a claimed key. The link already exists at this
+/// point, so a failure must not fail the request: after one retry the key is
+/// released instead, letting a client retry proceed rather than see 409 until
+/// the claim expires.
+async fn complete_idempotency_key(app_state: &AppState, idempotency_key: &str, short_code: &str) {
+    let mut result = app_state.db.complete_idempotency_key(idempotency_key, short_code).await;
+    if result.is_err() {
+        result = app_state.db.complete_idempotency_key(idempotency_key, short_code).await;
+    }
+    let Err(e) = result else {
+        return;
+    };
+
+    tracing::error!("Failed to complete idempotency key for {}: {}", short_code, e);
+    if let Err(release_error) = app_state.db.release_idempotency_key(idempotency_key).await {
+        tracing::warn!("Failed to release idempotency key: {}", release_error);
+    }
+}
+
+async fn create_or_reuse_link(
+    app_state: &AppState,
+    request: CreateLinkRequest,
+) -> Result<CreateLinkResponse, AppError> {
+    let deduplicate = CONFIG.deduplicate_urls
+        && request.custom_code.is_none()
+        && request.expires_in_hours.is_none()
+        && request.expires_at.is_none()
+        && request.activates_at.is_none()
+        && request.expired_redirect_url.is_none()
+        && !request.forward_query
+        && !request.forward_path
+        && request.redirect_status.is_none()
+        && request.geo_targets.is_empty()
+        && request.device_targets.is_empty()
+        && request.schedule_targets.is_empty()
+        && request.destinations.is_empty()
+        && !request.sticky_variants
+        && request.password.is_none()
+        && request.max_clicks.is_none();
+    
+    if deduplicate {
+        let duplicate = app_state
+            .db
+            .find_duplicate_link(&request.url, request.owner.as_deref(), CONFIG.default_redirect_status)
+            .await?;
+        if let Some(existing) = duplicate {
+            LINK_DEDUPLICATION_COUNT.inc();
+            return Ok(link_response(existing.short_code));
+        }
+    }
+    
+    let expires_at = match (request.expires_in_hours, request.expires_at) {
+        (Some(_), Some(_)) => {
+            return Err(AppError::InvalidRequest("Use either expires_in_hours or expires_at, not both".to_string()));
+        }
+        (Some(hours), None) => Some(expiry_from_hours(hours)),
+        (None, expires_at) => expires_at,
     };
+    validate_schedule(request.activates_at, expires_at, OffsetDateTime::now_utc())?;
+    
+    if let Some(expired_redirect_url) = &request.expired_redirect_url {
+        validate_url(expired_redirect_url, &app_state.url_policy)?;
+    }
+    
+    let mut geo_targets = request.geo_targets;
+    targeting::normalize_geo_targets(&mut geo_targets)?;
+    for target in &geo_targets {
+        validate_url(&target.url, &app_state.url_policy)?;
+    }
+    
+    targeting::validate_device_targets(&request.device_targets)?;
+    for target in &request.device_targets {
+        validate_url(&target.url, &app_state.url_policy)?;
+    }
+    
+    schedule::validate_schedule_targets(&request.schedule_targets)?;
+    for target in &request.schedule_targets {
+        validate_url(&target.url, &app_state.url_policy)?;
+    }
     
-    let expires_at = match request.expires_in_hours {
-        Some(hours) => Some(OffsetDateTime::now_utc() + time::Duration::hours(hours as i64)),
+    rotation::validate_destinations(&request.destinations, request.sticky_variants)?;
+    for destination in &request.destinations {
+        validate_url(&destination.url, &app_state.url_policy)?;
+    }
+    
+    if request.max_clicks.is_some_and(|max_clicks| max_clicks < 1) {
+        return Err(AppError::InvalidRequest("max_clicks must be at least 1".to_string()));
+    }
+    
+    let password_hash = match request.password {
+        Some(password) => {
+            password::validate_password(&password)?;
+            let hash = tokio::task::spawn_blocking(move || password::hash_password(&password))
+                .await
+                .map_err(|_| AppError::Internal)??;
+            Some(hash)
+        }
         None => None,
     };
     
-    let link = Link {
+    let mut link = Link {
         id: Uuid::new_v4(),
-        short_code: short_code.clone(),
-        original_url: request.url.clone(),
+        short_code: String::new(),
+        original_url: request.url,
         clicks: 0,
         created_at: OffsetDateTime::now_utc(),
         expires_at,
+        owner: request.owner,
+        is_custom: request.custom_code.is_some(),
+        password_hash,
+        max_clicks: request.max_clicks,
+        activates_at: request.activates_at,
+        expired_redirect_url: request.expired_redirect_url,
+        redirect_status: request.redirect_status.unwrap_or(CONFIG.default_redirect_status),
+        forward_query: request.forward_query,
+        forward_path: request.forward_path,
+        geo_targets: sqlx::types::Json(geo_targets),
+        device_targets: sqlx::types::Json(request.device_targets),
+        schedule_targets: sqlx::types::Json(request.schedule_targets),
+        destinations: sqlx::types::Json(request.destinations),
+        sticky_variants: request.sticky_variants,
     };
     
-    app_state.db.create_link(&link).await?;
-    
-    {
-        let mut cache = app_state.cache.lock().await;
-        if let Err(e) = cache.set(
-            &short_code, 
-            &request.url, 
-            std::time::Duration::from_secs(3600)
-        ).await {
-            tracing::warn!("Failed to cache link: {}", e);
+    if let Some(custom_code) = request.custom_code {
+        if !is_valid_custom_code(&custom_code) {
+            return Err(AppError::InvalidUrl("Invalid custom code format".to_string()));
         }
+        
+        match app_state.code_policy.check(&custom_code) {
+            Ok(()) => {}
+            Err(CodeRejection::Reserved) => {
+                return Err(AppError::InvalidRequest("Custom code is reserved".to_string()));
+            }
+            Err(CodeRejection::Blocked) => {
+                return Err(AppError::InvalidRequest("Custom code contains a blocked word".to_string()));
+            }
+        }
+        
+        link.short_code = custom_code;
+        app_state.db.create_link(&link).await?;
+    } else {
+        insert_with_generated_code(app_state, &mut link).await?;
+    }
+    
+    if let Err(e) = app_state.cache.set_link(&link, std::time::Duration::from_secs(3600)).await {
+        tracing::warn!("Failed to cache link: {}", e);
+        // Writing the link would have replaced any negative entry for this code;
+        // make sure a stale one can't hide the new link until it expires.
+        let _ = app_state.cache.delete(&link.short_code).await;
     }
     
     LINK_CREATION_COUNT.inc();
     
-    let short_url = format!("{}/{}", CONFIG.base_url, short_code);
+    Ok(link_response(link.short_code))
+}
+
+fn link_response(short_code: String) -> CreateLinkResponse {
+    CreateLinkResponse {
+        short_url: format!("{}/{}", CONFIG.base_url, short_code),
+        short_code,
+    }
+}
+
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<String>, AppError> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    
+    let key = value
+        .to_str()
+        .map(str::trim)
+        .map_err(|_| AppError::InvalidRequest("Idempotency-Key must be visible ASCII".to_string()))?;
     
-    Ok(Json(CreateLinkResponse {
-        short_url,
-        short_code: short_code.to_string(),
-    }))
-}
\ No newline at end of file
+    if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+        return Err(AppError::InvalidRequest(format!(
+            "Idempotency-Key must be between 1 and {} characters",
+            MAX_IDEMPOTENCY_KEY_LEN
+        )));
+    }
+    
+    Ok(Some(key.to_string()))
+}
+
+/// Identifies the request body, so a key replayed with different parameters is
+/// rejected instead of silently returning the first result.
+fn request_fingerprint(request: &CreateLinkRequest) -> String {
+    let body = serde_json::to_vec(request).expect("CreateLinkRequest always serializes");
+    Sha256::digest(&body).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Inserts `link` under a code from the configured generator, asking for a new
+/// one whenever the code is already taken.
+async fn insert_with_generated_code(app_state: &AppState, link: &mut Link) -> Result<(), AppError> {
+    let (mut collisions, mut filtered) = (0, 0);
+    for attempt in 0..CONFIG.short_code_max_attempts {
+        let sequence = if app_state.code_generator.uses_sequence() {
+            Some(app_state.db.next_code_sequence().await?)
+        } else {
+            None
+        };
+        
+        link.short_code = app_state.code_generator.generate(&CodeRequest {
+            url: &link.original_url,
+            attempt,
+            sequence,
+        });
+        
+        if app_state.code_policy.check(&link.short_code).is_err() {
+            SHORT_CODE_FILTERED.inc();
+            filtered += 1;
+            continue;
+        }
+        
+        match app_state.db.create_link(link).await {
+            Err(AppError::Conflict) => {
+                SHORT_CODE_COLLISIONS.inc();
+                collisions += 1;
+            }
+            result => return result,
+        }
+    }
+    
+    tracing::error!(
+        "Gave up generating a short code after {} attempts ({} collisions, {} reserved or blocked); \
+         consider raising SHORT_CODE_LENGTH",
+        CONFIG.short_code_max_attempts,
+        collisions,
+        filtered
+    );
+    Err(AppError::Internal)
+}
+
+/// Checks that `url` is a well-formed http(s) URL that `policy` lets links
+/// redirect to.
+pub fn validate_url(url: &str, policy: &UrlPolicy) -> Result<(), AppError> {
+    let parsed_url = Url::parse(url)
+        .map_err(|_| AppError::InvalidUrl("Invalid URL format".to_string()))?;
+    
+    if !matches!(parsed_url.scheme(), "http" | "https") {
+        return Err(AppError::InvalidUrl("Only HTTP and HTTPS URLs are allowed".to_string()));
+    }
+    
+    policy.check(&parsed_url).map_err(|rejection| {
+        DESTINATION_REJECTIONS.with_label_values(&[rejection.as_str()]).inc();
+        match rejection {
+            UrlRejection::PrivateAddress => AppError::PrivateDestination,
+            UrlRejection::SelfReference => AppError::RedirectLoop,
+            UrlRejection::NestedShortener => AppError::NestedShortener,
+        }
+    })
+}
+
+/// Checks that a link can ever be active: it must not have expired already and
+/// must expire after it activates.
+pub fn validate_schedule(
+    activates_at: Option<OffsetDateTime>,
+    expires_at: Option<OffsetDateTime>,
+    now: OffsetDateTime,
+) -> Result<(), AppError> {
+    if let Some(expires_at) = expires_at {
+        if expires_at <= now {
+            return Err(AppError::InvalidRequest("expires_at must be in the future".to_string()));
+        }
+        if activates_at.is_some_and(|activates_at| activates_at >= expires_at) {
+            return Err(AppError::InvalidRequest("activates_at must be before expires_at".to_string()));
+        }
+    }
+    Ok(())
+}
+
+pub fn expiry_from_hours(hours: i32) -> OffsetDateTime {
+    OffsetDateTime::now_utc() + time::Duration::hours(hours as i64)
+}
@@ -6,11 +6,20 @@ pub mod middleware;
 pub mod error;
 
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use services::{cache::CacheService, db::DbService};
+use models::link::Link;
+use services::{
+    cache::CacheStore, code_generator::CodeGenerator, code_policy::CodePolicy, db::LinkStore, geoip::GeoIp,
+    single_flight::SingleFlight, url_policy::UrlPolicy,
+};
 
 #[derive(Clone)]
 pub struct AppState {
-    pub db: Arc<DbService>,
-    pub cache: Arc<Mutex<CacheService>>,
+    pub db: Arc<dyn LinkStore>,
+    pub cache: Arc<dyn CacheStore>,
+    pub link_lookups: Arc<SingleFlight<Option<Link>>>,
+    pub code_generator: Arc<dyn CodeGenerator>,
+    pub code_policy: Arc<CodePolicy>,
+    pub url_policy: Arc<UrlPolicy>,
+    /// Country lookups for targeting and analytics; absent without `GEOIP_DATABASE`.
+    pub geoip: Option<Arc<GeoIp>>,
 } 
\ No newline at end of file
@@ -1,44 +1,31 @@
 use axum::{
+    middleware,
     routing::{get, post},
     Router,
 };
-use sqlx::postgres::PgPoolOptions;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::Mutex;
+use tokio::sync::watch;
 use tower_http::compression::CompressionLayer;
 use tower_http::cors::CorsLayer;
 use tower_http::set_header::SetResponseHeaderLayer;
 use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
 use axum::http::HeaderValue;
-use prometheus::{Encoder, TextEncoder, Counter, Histogram, register_counter, register_histogram};
-use once_cell::sync::Lazy;
+use prometheus::{Encoder, TextEncoder};
 use tower_governor::{governor::GovernorConfigBuilder, GovernorLayer};
 
-mod config;
-mod models;
-mod handlers;
-mod services;
-mod middleware;
-mod error;
-
-use services::{cache::CacheService, db::DbService};
-
-static REQUEST_COUNT: Lazy<Counter> = Lazy::new(|| {
-    register_counter!("link_shortener_requests_total", "Total number of requests").unwrap()
-});
-
-static RESPONSE_TIME: Lazy<Histogram> = Lazy::new(|| {
-    register_histogram!("link_shortener_response_time_seconds", "Response time in seconds").unwrap()
-});
-
-#[derive(Clone)]
-pub struct AppState {
-    pub db: Arc<DbService>,
-    pub cache: Arc<Mutex<CacheService>>,
-}
+use link_shortener_backend::{
+    config,
+    handlers,
+    middleware::metrics::track_metrics,
+    services::{
+        cache, click_counter::ClickFlusher, code_policy::CodePolicy, db, geoip::GeoIp,
+        single_flight::SingleFlight, url_policy::UrlPolicy,
+    },
+    AppState,
+};
 
 #[tokio::main]
 async fn main() {
@@ -46,30 +33,45 @@ async fn main() {
 
     let config = &config::CONFIG;
 
-    let pool = PgPoolOptions::new()
-        .max_connections(100)
-        .min_connections(10)
-        .max_lifetime(Duration::from_secs(1800))
-        .idle_timeout(Duration::from_secs(600))
-        .connect(&config.database_url)
+    let db_service = db::connect(&config.database_url)
         .await
-        .expect("Failed to create pool");
-
-    sqlx::migrate!("./migrations")
-        .run(&pool)
-        .await
-        .expect("Failed to migrate database");
-
-    let cache = CacheService::new(&config.redis_url)
-        .await
-        .expect("Failed to connect to Redis");
-
-    let db_service = Arc::new(DbService::new(pool));
-    let cache_service = Arc::new(Mutex::new(cache));
+        .expect("Failed to connect to the database");
+
+    let cache_service = cache::connect(
+        config.redis_url.as_deref(),
+        config.l1_cache_capacity,
+        Duration::from_secs(config.l1_cache_ttl_secs),
+    )
+    .await
+    .expect("Failed to connect to Redis");
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let click_flusher = ClickFlusher::new(
+        db_service.clone(),
+        cache_service.clone(),
+        Duration::from_secs(config.click_flush_interval_secs),
+        config.click_flush_batch_size,
+    );
+    let flusher_handle = tokio::spawn(click_flusher.run(shutdown_rx));
 
     let app_state = AppState {
         db: db_service,
         cache: cache_service,
+        link_lookups: Arc::new(SingleFlight::new()),
+        code_generator: config.short_code_strategy.build(
+            &config.short_code_alphabet,
+            config.short_code_length,
+            &config.short_code_secret,
+        ),
+        code_policy: Arc::new(CodePolicy::new(&config.reserved_codes, &config.blocked_words)),
+        url_policy: Arc::new(UrlPolicy::new(
+            &config.base_url,
+            &config.shortener_domains,
+            config.allow_private_destinations,
+        )),
+        geoip: config.geoip_database.as_ref().map(|path| {
+            Arc::new(GeoIp::open(path).expect("Failed to open the GeoIP database"))
+        }),
     };
 
     let governor_conf = Arc::new(
@@ -82,11 +84,25 @@ async fn main() {
 
     let app = Router::new()
         .route("/", post(handlers::shorten::create_link))
-        .route("/{code}", get(handlers::redirect::redirect))
+        .route("/{code}", get(handlers::redirect::redirect).post(handlers::redirect::unlock))
+        .route(
+            "/{code}/{*path}",
+            get(handlers::redirect::redirect_with_path).post(handlers::redirect::unlock_with_path),
+        )
         .route("/health", get(handlers::health::health_check))
         .route("/metrics", get(metrics_handler))
+        .route("/api/links", get(handlers::links::list_links))
+        .route(
+            "/api/links/{code}",
+            get(handlers::links::get_link)
+                .patch(handlers::links::update_link)
+                .delete(handlers::links::delete_link),
+        )
+        .route("/api/analytics", get(handlers::analytics::global_analytics))
+        .route("/api/analytics/{code}", get(handlers::analytics::link_analytics))
         .layer(
             ServiceBuilder::new()
+                .layer(middleware::from_fn(track_metrics))
                 .layer(TraceLayer::new_for_http())
                 .layer(CorsLayer::permissive())
                 .layer(CompressionLayer::new())
@@ -115,8 +131,40 @@ async fn main() {
         .expect("Failed to bind to address");
         
     axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown_signal())
         .await
         .expect("Failed to start server");
+
+    let _ = shutdown_tx.send(true);
+    if let Err(e) = flusher_handle.await {
+        tracing::error!("Click flusher task failed: {}", e);
+    }
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    tracing::info!("Shutdown signal received, draining connections");
 }
 
 async fn metrics_handler() -> String {
@@ -125,4 +173,4 @@ async fn metrics_handler() -> String {
     let mut buffer = vec![];
     encoder.encode(&metric_families, &mut buffer).unwrap();
     String::from_utf8(buffer).unwrap()
-} 
\ No newline at end of file
+}
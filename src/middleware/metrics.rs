@@ -0,0 +1,20 @@
+use axum::{extract::Request, middleware::Next, response::Response};
+use once_cell::sync::Lazy;
+use prometheus::{register_counter, register_histogram, Counter, Histogram};
+
+static REQUEST_COUNT: Lazy<Counter> = Lazy::new(|| {
+    register_counter!("link_shortener_requests_total", "Total number of requests").unwrap()
+});
+
+static RESPONSE_TIME: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!("link_shortener_response_time_seconds", "Response time in seconds").unwrap()
+});
+
+/// Counts every request and records how long it took to answer.
+pub async fn track_metrics(request: Request, next: Next) -> Response {
+    REQUEST_COUNT.inc();
+    let timer = RESPONSE_TIME.start_timer();
+    let response = next.run(request).await;
+    timer.observe_duration();
+    response
+}
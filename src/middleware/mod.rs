@@ -1 +1,2 @@
-pub mod rate_limit; 
\ No newline at end of file
+pub mod metrics;
+pub mod rate_limit;
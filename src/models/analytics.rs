@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+
+#[derive(Debug, Clone)]
+pub struct ClickEvent {
+    pub short_code: String,
+    pub clicked_at: OffsetDateTime,
+    pub referrer: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_hash: Option<String>,
+    pub country: Option<String>,
+    /// Targeting rule that picked the destination, e.g. `device:0` or `default`.
+    pub matched_rule: Option<String>,
+    /// Position of the A/B destination shown, when the link rotates between several.
+    pub variant: Option<i16>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnalyticsQuery {
+    pub days: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// Response shape consumed by the dashboard's `Analytics` component.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsResponse {
+    pub total_clicks: i64,
+    pub unique_clicks: i64,
+    pub clicks_by_date: Vec<DateClicks>,
+    pub clicks_by_country: Vec<CountryClicks>,
+    pub clicks_by_referrer: Vec<ReferrerClicks>,
+    pub clicks_by_rule: Vec<RuleClicks>,
+    /// Every A/B variant of the link, including those without clicks yet.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub clicks_by_variant: Vec<VariantClicks>,
+    pub top_links: Vec<TopLink>,
+}
+
+/// Instance-wide summary served when the dashboard has no short code selected.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GlobalAnalyticsResponse {
+    pub total_links: i64,
+    #[serde(flatten)]
+    pub analytics: AnalyticsResponse,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DateClicks {
+    pub date: String,
+    pub clicks: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CountryClicks {
+    pub country: String,
+    pub clicks: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReferrerClicks {
+    pub referrer: String,
+    pub clicks: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuleClicks {
+    pub rule: String,
+    pub clicks: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VariantClicks {
+    pub variant: i64,
+    pub url: String,
+    pub weight: u32,
+    pub clicks: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TopLink {
+    pub short_code: String,
+    pub original_url: String,
+    pub clicks: i64,
+}
@@ -1,8 +1,11 @@
-use serde::{Deserialize, Serialize};
-use sqlx::types::time::OffsetDateTime;
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::types::{time::OffsetDateTime, Json};
+use std::time::Duration;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+use crate::services::schedule::Schedule;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Link {
     pub id: Uuid,
     pub short_code: String,
@@ -12,17 +15,558 @@ pub struct Link {
     pub created_at: OffsetDateTime,
     #[serde(with = "time::serde::iso8601::option")]
     pub expires_at: Option<OffsetDateTime>,
+    pub owner: Option<String>,
+    pub is_custom: bool,
+    /// Argon2 PHC string; never serialized.
+    #[serde(skip)]
+    pub password_hash: Option<String>,
+    /// Clicks after which the link stops redirecting; `None` for no limit.
+    pub max_clicks: Option<i64>,
+    /// Before this moment the link exists but doesn't redirect yet.
+    #[serde(with = "time::serde::iso8601::option")]
+    pub activates_at: Option<OffsetDateTime>,
+    /// Where visitors go once the link has expired, instead of an error.
+    pub expired_redirect_url: Option<String>,
+    pub redirect_status: RedirectStatus,
+    /// Append the visitor's query parameters to the destination.
+    pub forward_query: bool,
+    /// Append anything after the code in the short URL to the destination path.
+    pub forward_path: bool,
+    /// Per-country destinations, checked in order; `original_url` is the fallback.
+    pub geo_targets: Json<Vec<GeoTarget>>,
+    /// Per-device/OS destinations, checked in order before geo targets.
+    pub device_targets: Json<Vec<DeviceTarget>>,
+    /// Time windows with their own destination, checked before any other rule.
+    pub schedule_targets: Json<Vec<ScheduleTarget>>,
+    /// Weighted variants that replace `original_url` for visitors no targeting
+    /// rule matched. Stored in `link_destinations`, in order.
+    pub destinations: Json<Vec<Destination>>,
+    /// Keep returning visitors on the variant they were first shown.
+    pub sticky_variants: bool,
 }
 
-#[derive(Debug, Deserialize)]
+/// One arm of an A/B split; receives `weight` out of the sum of all weights.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Destination {
+    pub url: String,
+    pub weight: u32,
+}
+
+/// Sends visitors from any of `countries` (ISO 3166-1 alpha-2) to `url`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeoTarget {
+    pub countries: Vec<String>,
+    pub url: String,
+}
+
+/// Sends visitors to `url` while the local time in `timezone` is inside the
+/// window `cron` describes: every minute the expression fires on belongs to
+/// it, so `* 9-17 * * MON-FRI` covers 09:00 to 17:59 on weekdays.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduleTarget {
+    pub cron: String,
+    /// IANA name such as `Europe/Berlin`.
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    pub url: String,
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+/// Sends visitors on a matching device and operating system to `url`. An
+/// omitted field matches any value, but a rule must name at least one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceTarget {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<DeviceKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<Platform>,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceKind {
+    Desktop,
+    Mobile,
+    Tablet,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Ios,
+    Android,
+    Windows,
+    Macos,
+    Linux,
+}
+
+/// HTTP status a link redirects with, serialized as the bare status code.
+/// Permanent redirects are cached by browsers and skip the service afterwards,
+/// so clicks go uncounted and later edits never reach those visitors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type)]
+#[serde(try_from = "u16", into = "u16")]
+#[repr(i16)]
+pub enum RedirectStatus {
+    MovedPermanently = 301,
+    #[default]
+    Found = 302,
+    TemporaryRedirect = 307,
+    PermanentRedirect = 308,
+}
+
+impl RedirectStatus {
+    pub fn is_permanent(self) -> bool {
+        matches!(self, Self::MovedPermanently | Self::PermanentRedirect)
+    }
+
+    /// The non-cacheable status with the same method semantics.
+    pub fn temporary(self) -> Self {
+        match self {
+            Self::MovedPermanently => Self::Found,
+            Self::PermanentRedirect => Self::TemporaryRedirect,
+            status => status,
+        }
+    }
+}
+
+impl From<RedirectStatus> for u16 {
+    fn from(status: RedirectStatus) -> Self {
+        status as u16
+    }
+}
+
+impl TryFrom<u16> for RedirectStatus {
+    type Error = String;
+
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        match code {
+            301 => Ok(Self::MovedPermanently),
+            302 => Ok(Self::Found),
+            307 => Ok(Self::TemporaryRedirect),
+            308 => Ok(Self::PermanentRedirect),
+            _ => Err(format!("unsupported redirect status {}, expected 301, 302, 307 or 308", code)),
+        }
+    }
+}
+
+impl std::str::FromStr for RedirectStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let code: u16 = value.parse().map_err(|_| format!("invalid redirect status {:?}", value))?;
+        Self::try_from(code)
+    }
+}
+
+/// Redirect-relevant subset of a `Link`, stored as JSON in the cache so the
+/// hit path can enforce the link's lifetime without going back to Postgres.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedLink {
+    pub url: String,
+    #[serde(default, with = "time::serde::timestamp::option")]
+    pub expires_at: Option<OffsetDateTime>,
+    /// Whether a password must be entered before redirecting.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub protected: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_clicks: Option<i64>,
+    #[serde(default, with = "time::serde::timestamp::option", skip_serializing_if = "Option::is_none")]
+    pub activates_at: Option<OffsetDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expired_redirect_url: Option<String>,
+    #[serde(default)]
+    pub redirect_status: RedirectStatus,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub forward_query: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub forward_path: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub geo_targets: Vec<GeoTarget>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub device_targets: Vec<DeviceTarget>,
+    /// Evaluated on every request, so a cached entry never pins the answer
+    /// from the window it was cached in.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule_targets: Vec<Schedule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub destinations: Vec<Destination>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sticky_variants: bool,
+}
+
+#[cfg(test)]
+impl Link {
+    /// Plain link with every optional feature off; tests override fields with
+    /// struct update syntax.
+    pub fn for_url(short_code: &str, url: &str) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            short_code: short_code.to_string(),
+            original_url: url.to_string(),
+            clicks: 0,
+            created_at: OffsetDateTime::now_utc(),
+            expires_at: None,
+            owner: None,
+            is_custom: false,
+            password_hash: None,
+            max_clicks: None,
+            activates_at: None,
+            expired_redirect_url: None,
+            redirect_status: RedirectStatus::Found,
+            forward_query: false,
+            forward_path: false,
+            geo_targets: Json(vec![]),
+            device_targets: Json(vec![]),
+            schedule_targets: Json(vec![]),
+            destinations: Json(vec![]),
+            sticky_variants: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStatus {
+    Pending,
+    Active,
+    Expired,
+}
+
+impl CachedLink {
+    /// Returns `None` for values that aren't a cached link, such as entries
+    /// written by older versions that stored the bare URL.
+    pub fn decode(value: &str) -> Option<Self> {
+        serde_json::from_str(value).ok()
+    }
+
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).expect("CachedLink always serializes")
+    }
+
+    pub fn status(&self, now: OffsetDateTime) -> LinkStatus {
+        match (self.activates_at, self.expires_at) {
+            (_, Some(expires_at)) if expires_at <= now => LinkStatus::Expired,
+            (Some(activates_at), _) if activates_at > now => LinkStatus::Pending,
+            _ => LinkStatus::Active,
+        }
+    }
+
+    /// How long the entry may be cached: `max_ttl`, clamped to the remaining
+    /// lifetime. `None` once the link has expired.
+    pub fn cache_ttl(&self, max_ttl: Duration, now: OffsetDateTime) -> Option<Duration> {
+        let Some(expires_at) = self.expires_at else {
+            return Some(max_ttl);
+        };
+
+        let remaining = Duration::try_from(expires_at - now).ok()?;
+        (!remaining.is_zero()).then(|| remaining.min(max_ttl))
+    }
+}
+
+#[cfg(test)]
+impl CachedLink {
+    pub fn for_url(url: &str) -> Self {
+        Self::from(&Link::for_url("test", url))
+    }
+}
+
+impl From<&Link> for CachedLink {
+    fn from(link: &Link) -> Self {
+        Self {
+            url: link.original_url.clone(),
+            expires_at: link.expires_at,
+            protected: link.password_hash.is_some(),
+            max_clicks: link.max_clicks,
+            activates_at: link.activates_at,
+            expired_redirect_url: link.expired_redirect_url.clone(),
+            redirect_status: link.redirect_status,
+            forward_query: link.forward_query,
+            forward_path: link.forward_path,
+            geo_targets: link.geo_targets.0.clone(),
+            device_targets: link.device_targets.0.clone(),
+            schedule_targets: link.schedule_targets.iter().cloned().map(Schedule::from).collect(),
+            destinations: link.destinations.0.clone(),
+            sticky_variants: link.sticky_variants,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateLinkRequest {
     pub url: String,
     pub custom_code: Option<String>,
     pub expires_in_hours: Option<i32>,
+    /// RFC 3339 moment the link starts redirecting.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub activates_at: Option<OffsetDateTime>,
+    /// RFC 3339 moment the link stops redirecting; alternative to `expires_in_hours`.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+    pub expired_redirect_url: Option<String>,
+    /// 301, 302, 307 or 308; defaults to `DEFAULT_REDIRECT_STATUS`.
+    pub redirect_status: Option<RedirectStatus>,
+    #[serde(default)]
+    pub forward_query: bool,
+    #[serde(default)]
+    pub forward_path: bool,
+    #[serde(default)]
+    pub geo_targets: Vec<GeoTarget>,
+    #[serde(default)]
+    pub device_targets: Vec<DeviceTarget>,
+    #[serde(default)]
+    pub schedule_targets: Vec<ScheduleTarget>,
+    #[serde(default)]
+    pub destinations: Vec<Destination>,
+    #[serde(default)]
+    pub sticky_variants: bool,
+    pub owner: Option<String>,
+    pub password: Option<String>,
+    pub max_clicks: Option<i64>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct CreateLinkResponse {
     pub short_url: String,
     pub short_code: String,
-} 
\ No newline at end of file
+}
+
+/// A link as the management API and dashboard see it. Storage-only state such
+/// as the password hash stays out.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkResponse {
+    pub id: Uuid,
+    pub short_code: String,
+    pub short_url: String,
+    pub original_url: String,
+    pub clicks: i64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub activates_at: Option<OffsetDateTime>,
+    pub owner: Option<String>,
+    pub max_clicks: Option<i64>,
+    pub expired_redirect_url: Option<String>,
+    pub redirect_status: RedirectStatus,
+    pub forward_query: bool,
+    pub forward_path: bool,
+    pub geo_targets: Vec<GeoTarget>,
+    pub device_targets: Vec<DeviceTarget>,
+    pub schedule_targets: Vec<ScheduleTarget>,
+    pub destinations: Vec<Destination>,
+    pub sticky_variants: bool,
+}
+
+impl LinkResponse {
+    pub fn new(link: Link, base_url: &str) -> Self {
+        Self {
+            id: link.id,
+            short_url: format!("{}/{}", base_url, link.short_code),
+            short_code: link.short_code,
+            original_url: link.original_url,
+            clicks: link.clicks,
+            created_at: link.created_at,
+            expires_at: link.expires_at,
+            activates_at: link.activates_at,
+            owner: link.owner,
+            max_clicks: link.max_clicks,
+            expired_redirect_url: link.expired_redirect_url,
+            redirect_status: link.redirect_status,
+            forward_query: link.forward_query,
+            forward_path: link.forward_path,
+            geo_targets: link.geo_targets.0,
+            device_targets: link.device_targets.0,
+            schedule_targets: link.schedule_targets.0,
+            destinations: link.destinations.0,
+            sticky_variants: link.sticky_variants,
+        }
+    }
+}
+
+/// A previously seen `Idempotency-Key`; `short_code` is unset while the original
+/// request is still being processed.
+#[derive(Debug)]
+pub struct IdempotencyRecord {
+    pub fingerprint: String,
+    pub short_code: Option<String>,
+}
+
+/// Partial update for an existing link. Omitted fields are left untouched;
+/// `"expires_in_hours": null` or `"expires_at": null` removes the expiry
+/// altogether.
+#[derive(Debug, Deserialize)]
+pub struct UpdateLinkRequest {
+    pub url: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub expires_in_hours: Option<Option<i32>>,
+    /// RFC 3339 moment the link stops redirecting; alternative to `expires_in_hours`.
+    #[serde(default, deserialize_with = "deserialize_some_rfc3339")]
+    pub expires_at: Option<Option<OffsetDateTime>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListLinksQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+fn deserialize_some_rfc3339<'de, D>(deserializer: D) -> Result<Option<Option<OffsetDateTime>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    time::serde::rfc3339::option::deserialize(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn cached(expires_at: Option<OffsetDateTime>) -> CachedLink {
+        CachedLink { expires_at, ..CachedLink::for_url("https://example.com") }
+    }
+
+    #[test]
+    fn test_cached_link_round_trip() {
+        let link = cached(Some(datetime!(2030-01-01 0:00 UTC)));
+        assert_eq!(CachedLink::decode(&link.encode()), Some(link));
+    }
+
+    #[test]
+    fn test_protected_flag_round_trip() {
+        let link = CachedLink { protected: true, ..cached(None) };
+        assert_eq!(CachedLink::decode(&link.encode()), Some(link));
+        assert!(!cached(None).encode().contains("protected"));
+    }
+
+    #[test]
+    fn test_redirect_status_serializes_as_code() {
+        assert_eq!(serde_json::to_string(&RedirectStatus::TemporaryRedirect).unwrap(), "307");
+        assert_eq!(serde_json::from_str::<RedirectStatus>("301").unwrap(), RedirectStatus::MovedPermanently);
+        assert!(serde_json::from_str::<RedirectStatus>("303").is_err());
+        assert_eq!("308".parse::<RedirectStatus>(), Ok(RedirectStatus::PermanentRedirect));
+    }
+
+    #[test]
+    fn test_redirect_status_temporary_counterpart() {
+        assert_eq!(RedirectStatus::MovedPermanently.temporary(), RedirectStatus::Found);
+        assert_eq!(RedirectStatus::PermanentRedirect.temporary(), RedirectStatus::TemporaryRedirect);
+        assert_eq!(RedirectStatus::Found.temporary(), RedirectStatus::Found);
+        assert!(!RedirectStatus::TemporaryRedirect.is_permanent());
+    }
+
+    #[test]
+    fn test_device_target_parses_lowercase_names() {
+        let target: DeviceTarget = serde_json::from_str(r#"{"device":"mobile","os":"ios","url":"https://apps.apple.com"}"#).unwrap();
+        assert_eq!(target.device, Some(DeviceKind::Mobile));
+        assert_eq!(target.os, Some(Platform::Ios));
+
+        let target: DeviceTarget = serde_json::from_str(r#"{"device":"desktop","url":"https://example.com"}"#).unwrap();
+        assert_eq!(serde_json::to_string(&target).unwrap(), r#"{"device":"desktop","url":"https://example.com"}"#);
+        assert!(serde_json::from_str::<DeviceTarget>(r#"{"os":"symbian","url":"https://example.com"}"#).is_err());
+    }
+
+    #[test]
+    fn test_schedule_target_defaults_to_utc() {
+        let target: ScheduleTarget = serde_json::from_str(r#"{"cron":"* 9-17 * * MON-FRI","url":"https://example.com/chat"}"#).unwrap();
+        assert_eq!(target.timezone, "UTC");
+
+        let link = CachedLink { schedule_targets: vec![Schedule::from(target)], ..cached(None) };
+        assert_eq!(CachedLink::decode(&link.encode()), Some(link));
+    }
+
+    #[test]
+    fn test_link_response_shape() {
+        let link = Link {
+            password_hash: Some("$argon2id$hash".to_string()),
+            created_at: datetime!(2025-01-01 12:00 UTC),
+            ..Link::for_url("abc", "https://example.com")
+        };
+        let json = serde_json::to_value(LinkResponse::new(link, "https://sho.rt")).unwrap();
+
+        assert_eq!(json["shortCode"], "abc");
+        assert_eq!(json["shortUrl"], "https://sho.rt/abc");
+        assert_eq!(json["originalUrl"], "https://example.com");
+        assert_eq!(json["createdAt"], "2025-01-01T12:00:00Z");
+        assert_eq!(json["expiresAt"], serde_json::Value::Null);
+        assert!(!json.to_string().contains("argon2"));
+    }
+
+    #[test]
+    fn test_update_request_tells_null_from_missing_expiry() {
+        let update: UpdateLinkRequest = serde_json::from_str(r#"{"url":"https://example.com"}"#).unwrap();
+        assert_eq!((update.expires_in_hours, update.expires_at), (None, None));
+
+        let update: UpdateLinkRequest = serde_json::from_str(r#"{"expires_at":null}"#).unwrap();
+        assert_eq!(update.expires_at, Some(None));
+
+        let update: UpdateLinkRequest = serde_json::from_str(r#"{"expires_at":"2030-01-01T00:00:00Z"}"#).unwrap();
+        assert_eq!(update.expires_at, Some(Some(datetime!(2030-01-01 0:00 UTC))));
+    }
+
+    #[test]
+    fn test_decode_rejects_legacy_plain_url() {
+        assert_eq!(CachedLink::decode("https://example.com"), None);
+    }
+
+    #[test]
+    fn test_status() {
+        let now = datetime!(2025-01-01 12:00 UTC);
+
+        assert_eq!(cached(None).status(now), LinkStatus::Active);
+        assert_eq!(cached(Some(datetime!(2025-01-01 13:00 UTC))).status(now), LinkStatus::Active);
+        assert_eq!(cached(Some(now)).status(now), LinkStatus::Expired);
+    }
+
+    #[test]
+    fn test_status_before_activation() {
+        let now = datetime!(2025-01-01 12:00 UTC);
+        let scheduled = |activates_at| CachedLink { activates_at: Some(activates_at), ..cached(Some(datetime!(2025-01-02 0:00 UTC))) };
+
+        assert_eq!(scheduled(datetime!(2025-01-01 13:00 UTC)).status(now), LinkStatus::Pending);
+        assert_eq!(scheduled(now).status(now), LinkStatus::Active);
+        assert_eq!(CachedLink { expires_at: Some(now), ..scheduled(datetime!(2025-01-01 13:00 UTC)) }.status(now), LinkStatus::Expired);
+    }
+
+    #[test]
+    fn test_create_request_parses_rfc3339_schedule() {
+        let request: CreateLinkRequest = serde_json::from_str(
+            r#"{"url":"https://example.com","activates_at":"2030-01-01T09:00:00+01:00","expires_at":"2030-02-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(request.activates_at, Some(datetime!(2030-01-01 8:00 UTC)));
+        assert_eq!(request.expires_at, Some(datetime!(2030-02-01 0:00 UTC)));
+        assert!(serde_json::from_str::<CreateLinkRequest>(r#"{"url":"https://example.com","expires_at":"tomorrow"}"#).is_err());
+    }
+
+    #[test]
+    fn test_cache_ttl_is_clamped_to_expiry() {
+        let now = datetime!(2025-01-01 12:00 UTC);
+        let max_ttl = Duration::from_secs(86400);
+
+        assert_eq!(cached(None).cache_ttl(max_ttl, now), Some(max_ttl));
+        assert_eq!(
+            cached(Some(datetime!(2025-01-01 12:10 UTC))).cache_ttl(max_ttl, now),
+            Some(Duration::from_secs(600))
+        );
+        assert_eq!(
+            cached(Some(datetime!(2025-03-01 0:00 UTC))).cache_ttl(max_ttl, now),
+            Some(max_ttl)
+        );
+        assert_eq!(cached(Some(datetime!(2025-01-01 11:00 UTC))).cache_ttl(max_ttl, now), None);
+    }
+}
@@ -1 +1,2 @@
-pub mod link; 
\ No newline at end of file
+pub mod link;
+pub mod analytics;
\ No newline at end of file
@@ -0,0 +1,153 @@
+use axum::http::{header, HeaderMap};
+use ipnetwork::IpNetwork;
+use sha2::{Digest, Sha256};
+use sqlx::types::time::OffsetDateTime;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::config::CONFIG;
+use crate::models::analytics::ClickEvent;
+
+const MAX_HEADER_VALUE_LEN: usize = 512;
+
+/// `country` is the visitor's ISO code when GeoIP resolved one; `matched_rule`
+/// names the targeting rule that picked the destination and `variant` the A/B
+/// destination shown, if the link rotates.
+pub fn click_event_from_request(
+    short_code: &str,
+    headers: &HeaderMap,
+    peer: SocketAddr,
+    country: Option<String>,
+    matched_rule: String,
+    variant: Option<i16>,
+) -> ClickEvent {
+    let ip = client_ip(headers, peer, &CONFIG.trusted_proxies);
+
+    ClickEvent {
+        short_code: short_code.to_string(),
+        clicked_at: OffsetDateTime::now_utc(),
+        referrer: header_value(headers, header::REFERER),
+        user_agent: header_value(headers, header::USER_AGENT),
+        ip_hash: Some(hash_ip(&ip, CONFIG.ip_hash_salt.as_bytes())),
+        country,
+        matched_rule: Some(matched_rule),
+        variant,
+    }
+}
+
+/// Resolves the visitor address. `X-Forwarded-For` is only followed while the
+/// hop that sent it is one of `trusted_proxies`: walking the header from the
+/// right, the first untrusted address is the client, since anything to its
+/// left was written by the client itself.
+pub fn client_ip(headers: &HeaderMap, peer: SocketAddr, trusted_proxies: &[IpNetwork]) -> IpAddr {
+    let is_trusted = |ip: IpAddr| trusted_proxies.iter().any(|network| network.contains(ip));
+    let hops = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+
+    let mut client = peer.ip();
+    for hop in hops.iter().rev() {
+        if !is_trusted(client) {
+            break;
+        }
+        match hop.trim().parse() {
+            Ok(ip) => client = ip,
+            Err(_) => break,
+        }
+    }
+
+    client
+}
+
+pub fn hash_ip(ip: &IpAddr, salt: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(ip.to_string().as_bytes());
+    let result = hasher.finalize();
+
+    result[..16].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn header_value(headers: &HeaderMap, name: header::HeaderName) -> Option<String> {
+    let value = headers.get(name)?.to_str().ok()?.trim();
+    if value.is_empty() {
+        return None;
+    }
+
+    Some(value.chars().take(MAX_HEADER_VALUE_LEN).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn peer() -> SocketAddr {
+        "10.0.0.1:4000".parse().unwrap()
+    }
+
+    fn trusted() -> Vec<IpNetwork> {
+        vec!["10.0.0.0/8".parse().unwrap()]
+    }
+
+    fn forwarded_for(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_client_ip_falls_back_to_peer() {
+        let headers = HeaderMap::new();
+        assert_eq!(client_ip(&headers, peer(), &trusted()), peer().ip());
+    }
+
+    #[test]
+    fn test_client_ip_prefers_forwarded_for() {
+        let headers = forwarded_for("203.0.113.7, 10.0.0.2");
+        assert_eq!(client_ip(&headers, peer(), &trusted()), "203.0.113.7".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_client_ip_ignores_forwarded_for_from_untrusted_peer() {
+        let headers = forwarded_for("203.0.113.7");
+        assert_eq!(client_ip(&headers, peer(), &[]), peer().ip());
+
+        let direct: SocketAddr = "198.51.100.4:4000".parse().unwrap();
+        assert_eq!(client_ip(&headers, direct, &trusted()), direct.ip());
+    }
+
+    #[test]
+    fn test_client_ip_stops_at_first_untrusted_hop() {
+        // The client prepended a spoofed address; the proxy appended the real one.
+        let headers = forwarded_for("1.2.3.4, 203.0.113.7");
+        assert_eq!(client_ip(&headers, peer(), &trusted()), "203.0.113.7".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_client_ip_ignores_garbage_forwarded_for() {
+        let headers = forwarded_for("not-an-ip");
+        assert_eq!(client_ip(&headers, peer(), &trusted()), peer().ip());
+    }
+
+    #[test]
+    fn test_hash_ip_is_salted_and_stable() {
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+
+        assert_eq!(hash_ip(&ip, b"salt"), hash_ip(&ip, b"salt"));
+        assert_ne!(hash_ip(&ip, b"salt"), hash_ip(&ip, b"other"));
+        assert_eq!(hash_ip(&ip, b"salt").len(), 32);
+    }
+
+    #[test]
+    fn test_header_value_truncates_and_skips_empty() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::REFERER, HeaderValue::from_static("  "));
+        headers.insert(header::USER_AGENT, HeaderValue::from_str(&"a".repeat(600)).unwrap());
+
+        assert_eq!(header_value(&headers, header::REFERER), None);
+        assert_eq!(header_value(&headers, header::USER_AGENT).unwrap().len(), MAX_HEADER_VALUE_LEN);
+    }
+}
@@ -1,66 +1,138 @@
-use redis::aio::ConnectionManager;
-use redis::{AsyncCommands, RedisResult, Pipeline, cmd};
+mod memory;
+mod redis;
+
+use async_trait::async_trait;
+use ::redis::RedisResult;
+use once_cell::sync::Lazy;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use std::sync::Arc;
 use std::time::Duration;
+use time::OffsetDateTime;
 
-pub struct CacheService {
-    conn: ConnectionManager,
-}
+use crate::models::link::{CachedLink, Link};
+use crate::services::local_cache::LocalCache;
 
-impl CacheService {
-    pub async fn new(redis_url: &str) -> Result<Self, redis::RedisError> {
-        let client = redis::Client::open(redis_url)?;
-        let conn = ConnectionManager::new(client).await?;
-        
-        let cache = Self { conn };
-        Ok(cache)
-    }
+pub use memory::MemoryCache;
+pub use self::redis::RedisCache;
 
-    pub async fn get(&mut self, key: &str) -> RedisResult<Option<String>> {
-        self.conn.get(key).await
-    }
+pub const DEFAULT_TTL: Duration = Duration::from_secs(86400);
 
-    pub async fn set(&mut self, key: &str, value: &str, ttl: Duration) -> RedisResult<()> {
-        self.conn.set_ex(key, value, ttl.as_secs()).await
-    }
+static CACHE_HITS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("cache_hits_total", "Cache hits by tier", &["tier"])
+        .expect("Failed to register prometheus counter")
+});
 
-    pub async fn set_with_default_ttl(&mut self, key: &str, value: &str) -> RedisResult<()> {
-        self.set(key, value, Duration::from_secs(86400)).await
-    }
+static CACHE_MISSES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("cache_misses_total", "Cache misses by tier", &["tier"])
+        .expect("Failed to register prometheus counter")
+});
 
-    pub async fn incr(&mut self, key: &str) -> RedisResult<i64> {
-        self.conn.incr(key, 1).await
-    }
+/// Stored in place of a value to remember that a key has nothing behind it.
+/// Contains a NUL byte, so it can never collide with a URL.
+const NEGATIVE_ENTRY: &str = "\0missing";
 
-    pub async fn batch_get(&mut self, keys: &[String]) -> RedisResult<Vec<Option<String>>> {
-        if keys.is_empty() {
-            return Ok(vec![]);
-        }
-        self.conn.get(keys).await
-    }
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheEntry {
+    Present { value: String, ttl: Option<Duration> },
+    Absent,
+}
 
-    pub async fn batch_set(&mut self, key_values: &[(String, String)], ttl: Duration) -> RedisResult<()> {
-        if key_values.is_empty() {
-            return Ok(());
+/// Everything the service keeps outside the database: the redirect cache,
+/// buffered click counters and the per-day leaderboards. Nothing here is the
+/// source of truth, so callers treat errors as misses and carry on.
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    /// Distinguishes negative entries from plain misses and reports the
+    /// remaining TTL where the backend knows it.
+    async fn lookup(&self, key: &str) -> RedisResult<Option<CacheEntry>>;
+
+    async fn get(&self, key: &str) -> RedisResult<Option<String>> {
+        match self.lookup(key).await? {
+            Some(CacheEntry::Present { value, .. }) => Ok(Some(value)),
+            Some(CacheEntry::Absent) | None => Ok(None),
         }
-        
-        let mut pipe = Pipeline::new();
-        for (key, value) in key_values {
-            pipe.set_ex(key, value, ttl.as_secs());
-        }
-        pipe.query_async(&mut self.conn).await
     }
 
-    pub async fn exists(&mut self, key: &str) -> RedisResult<bool> {
-        self.conn.exists(key).await
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> RedisResult<()>;
+
+    async fn set_with_default_ttl(&self, key: &str, value: &str) -> RedisResult<()> {
+        self.set(key, value, DEFAULT_TTL).await
     }
 
-    pub async fn delete(&mut self, key: &str) -> RedisResult<i64> {
-        self.conn.del(key).await
+    /// Caches the redirect payload for `link` under its short code, for at most
+    /// `max_ttl` and never past the link's own expiry.
+    async fn set_link(&self, link: &Link, max_ttl: Duration) -> RedisResult<()> {
+        let cached = CachedLink::from(link);
+        match cached.cache_ttl(max_ttl, OffsetDateTime::now_utc()) {
+            Some(ttl) => self.set(&link.short_code, &cached.encode(), ttl).await,
+            None => self.delete(&link.short_code).await,
+        }
     }
 
-    pub async fn get_connection_info(&mut self) -> RedisResult<String> {
-        cmd("INFO").arg("clients").query_async(&mut self.conn).await
+    /// Records that `key` has no value.
+    async fn set_negative(&self, key: &str, ttl: Duration) -> RedisResult<()>;
+
+    async fn delete(&self, key: &str) -> RedisResult<()>;
+
+    /// Adds `by` to a counter that disappears `ttl` after its last update,
+    /// returning the new value. `by` may be zero to read it.
+    async fn incr_with_ttl(&self, key: &str, by: i64, ttl: Duration) -> RedisResult<i64>;
+
+    /// Decrements a countdown, first setting it to `initial` (expiring after
+    /// `ttl`) if it doesn't exist. Returns the new value, which goes negative
+    /// once the countdown is used up.
+    async fn countdown(&self, key: &str, initial: i64, ttl: Duration) -> RedisResult<i64>;
+
+    /// Increments `key` and records `member` in `set_key` atomically, so a
+    /// consumer draining the set never misses a counter.
+    async fn incr_tracked(&self, key: &str, set_key: &str, member: &str, by: i64) -> RedisResult<()>;
+
+    /// Removes up to `limit` members from `set_key` and atomically takes their
+    /// counters, stored under `key_prefix` followed by the member.
+    async fn drain_tracked(&self, set_key: &str, key_prefix: &str, limit: usize) -> RedisResult<Vec<(String, i64)>>;
+
+    async fn zincr(&self, key: &str, member: &str, ttl: Duration) -> RedisResult<()>;
+
+    /// Unions `keys` into `dest` (kept for `ttl`) unless it already exists, then
+    /// returns the `limit` highest scoring members.
+    async fn zunion_top(
+        &self,
+        dest: &str,
+        keys: &[String],
+        ttl: Duration,
+        limit: usize,
+    ) -> RedisResult<Vec<(String, i64)>>;
+}
+
+/// Connects to Redis, fronted by an L1 of `l1_capacity` entries kept for at
+/// most `l1_ttl`, when `redis_url` is set. Otherwise everything stays in
+/// process, which is only correct for a single instance. A configured Redis
+/// must be reachable at startup: quietly running on a per-instance cache would
+/// let instances disagree about links for the life of the process. Once
+/// connected, outages are ridden out by the reconnecting `RedisCache`.
+pub async fn connect(
+    redis_url: Option<&str>,
+    l1_capacity: u64,
+    l1_ttl: Duration,
+) -> RedisResult<Arc<dyn CacheStore>> {
+    let Some(redis_url) = redis_url else {
+        tracing::info!("REDIS_URL not set; using the in-process cache");
+        return Ok(Arc::new(MemoryCache::new(l1_capacity, DEFAULT_TTL)));
+    };
+
+    let cache = RedisCache::new(redis_url, LocalCache::new(l1_capacity, l1_ttl)).await?;
+    Ok(Arc::new(cache))
+}
+
+/// Probabilistic early expiration (XFetch): as `remaining` approaches `window`,
+/// an increasing share of readers refresh the entry ahead of time, so a hot key
+/// is reloaded by a few requests instead of every instance at once on expiry.
+/// `sample` must be uniform in (0, 1]; a zero `window` disables early refresh.
+pub fn should_refresh_early(remaining: Duration, window: Duration, sample: f64) -> bool {
+    if window.is_zero() || sample <= 0.0 {
+        return false;
     }
+    window.as_secs_f64() * -sample.ln() >= remaining.as_secs_f64()
 }
 
 #[cfg(test)]
@@ -68,63 +140,39 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_batch_get_empty_keys() {
-        let keys: Vec<String> = vec![];
-        assert_eq!(keys.is_empty(), true);
+    fn test_negative_entry_is_never_a_url() {
+        assert!(url::Url::parse(NEGATIVE_ENTRY).is_err());
+        assert!(NEGATIVE_ENTRY.contains('\0'));
     }
 
     #[test]
-    fn test_batch_set_empty_keys() {
-        let key_values: Vec<(String, String)> = vec![];
-        assert_eq!(key_values.is_empty(), true);
+    fn test_should_refresh_early_disabled_window() {
+        assert!(!should_refresh_early(Duration::ZERO, Duration::ZERO, 0.01));
     }
 
     #[test]
-    fn test_ttl_conversion() {
-        let ttl = Duration::from_secs(60);
-        assert_eq!(ttl.as_secs(), 60);
-        
-        let default_ttl = Duration::from_secs(86400);
-        assert_eq!(default_ttl.as_secs(), 86400);
+    fn test_should_refresh_early_far_from_expiry() {
+        let window = Duration::from_secs(30);
+        assert!(!should_refresh_early(Duration::from_secs(86400), window, 0.5));
     }
 
     #[test]
-    fn test_redis_url_parsing() {
-        let redis_url = "redis://127.0.0.1:6379";
-        assert!(redis_url.starts_with("redis://"));
-        assert!(redis_url.contains("6379"));
+    fn test_should_refresh_early_near_expiry() {
+        let window = Duration::from_secs(30);
+        assert!(should_refresh_early(Duration::from_secs(1), window, 0.5));
+        assert!(!should_refresh_early(Duration::from_secs(29), window, 0.99));
     }
 
-    #[test]
-    fn test_key_value_pair_construction() {
-        let key = "test_key";
-        let value = "test_value";
-        let pair = (key.to_string(), value.to_string());
-        
-        assert_eq!(pair.0, "test_key");
-        assert_eq!(pair.1, "test_value");
+    #[tokio::test]
+    async fn test_unreachable_redis_fails_to_connect() {
+        assert!(connect(Some("redis://127.0.0.1:1"), 100, Duration::from_secs(30)).await.is_err());
     }
 
-    #[test]
-    fn test_pipeline_key_value_operations() {
-        let key_values = vec![
-            ("key1".to_string(), "value1".to_string()),
-            ("key2".to_string(), "value2".to_string()),
-        ];
-        
-        assert_eq!(key_values.len(), 2);
-        assert_eq!(key_values[0].0, "key1");
-        assert_eq!(key_values[1].1, "value2");
-    }
+    #[tokio::test]
+    async fn test_no_redis_url_uses_memory() {
+        let cache = connect(None, 100, Duration::from_secs(30)).await.unwrap();
 
-    #[test]
-    fn test_redis_command_construction() {
-        let key = "test_key";
-        let value = "test_value";
-        let ttl_secs = 3600u64;
-        
-        assert!(!key.is_empty());
-        assert!(!value.is_empty());
-        assert!(ttl_secs > 0);
+        cache.set("abc", "https://example.com", Duration::from_secs(60)).await.unwrap();
+        assert_eq!(cache.get("abc").await.unwrap().as_deref(), Some("https://example.com"));
     }
-} 
\ No newline at end of file
+}
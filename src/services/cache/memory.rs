@@ -0,0 +1,259 @@
+use async_trait::async_trait;
+use redis::RedisResult;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::services::cache::{CacheEntry, CacheStore, CACHE_HITS, CACHE_MISSES, NEGATIVE_ENTRY};
+use crate::services::local_cache::LocalCache;
+
+struct SortedSet {
+    scores: HashMap<String, i64>,
+    expires_at: Instant,
+}
+
+#[derive(Default)]
+struct Counters {
+    values: HashMap<String, i64>,
+    expiring: HashMap<String, (i64, Instant)>,
+    tracked: HashMap<String, HashSet<String>>,
+    sorted_sets: HashMap<String, SortedSet>,
+}
+
+/// In-process stand-in for Redis when none is configured. Values (including
+/// tombstones) live in a bounded `LocalCache`; counters and leaderboards are
+/// kept in plain maps and only exist on this instance, so it suits a single
+/// node. Operations never fail.
+pub struct MemoryCache {
+    values: LocalCache,
+    counters: Mutex<Counters>,
+}
+
+impl MemoryCache {
+    /// Holds up to `capacity` values, none for longer than `max_ttl`.
+    pub fn new(capacity: u64, max_ttl: Duration) -> Self {
+        Self {
+            values: LocalCache::new(capacity, max_ttl),
+            counters: Mutex::new(Counters::default()),
+        }
+    }
+}
+
+#[async_trait]
+impl CacheStore for MemoryCache {
+    async fn lookup(&self, key: &str) -> RedisResult<Option<CacheEntry>> {
+        let Some(value) = self.values.get(key) else {
+            CACHE_MISSES.with_label_values(&["memory"]).inc();
+            return Ok(None);
+        };
+
+        if value == NEGATIVE_ENTRY {
+            CACHE_HITS.with_label_values(&["negative"]).inc();
+            return Ok(Some(CacheEntry::Absent));
+        }
+
+        CACHE_HITS.with_label_values(&["memory"]).inc();
+        Ok(Some(CacheEntry::Present { value, ttl: None }))
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> RedisResult<()> {
+        self.values.insert(key, value, ttl);
+        Ok(())
+    }
+
+    async fn set_negative(&self, key: &str, ttl: Duration) -> RedisResult<()> {
+        self.values.insert(key, NEGATIVE_ENTRY, ttl);
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> RedisResult<()> {
+        self.values.invalidate(key);
+        let mut counters = self.counters.lock().unwrap();
+        counters.values.remove(key);
+        counters.expiring.remove(key);
+        Ok(())
+    }
+
+    async fn incr_with_ttl(&self, key: &str, by: i64, ttl: Duration) -> RedisResult<i64> {
+        let mut counters = self.counters.lock().unwrap();
+        let now = Instant::now();
+        counters.expiring.retain(|_, (_, expires_at)| *expires_at > now);
+
+        let (count, expires_at) = counters.expiring.entry(key.to_string()).or_insert((0, now));
+        *count += by;
+        *expires_at = now + ttl;
+        Ok(*count)
+    }
+
+    async fn countdown(&self, key: &str, initial: i64, ttl: Duration) -> RedisResult<i64> {
+        let mut counters = self.counters.lock().unwrap();
+        let now = Instant::now();
+        counters.expiring.retain(|_, (_, expires_at)| *expires_at > now);
+
+        let (remaining, _) = counters.expiring.entry(key.to_string()).or_insert((initial, now + ttl));
+        *remaining -= 1;
+        Ok(*remaining)
+    }
+
+    async fn incr_tracked(&self, key: &str, set_key: &str, member: &str, by: i64) -> RedisResult<()> {
+        let mut counters = self.counters.lock().unwrap();
+        *counters.values.entry(key.to_string()).or_default() += by;
+        counters
+            .tracked
+            .entry(set_key.to_string())
+            .or_default()
+            .insert(member.to_string());
+        Ok(())
+    }
+
+    async fn drain_tracked(&self, set_key: &str, key_prefix: &str, limit: usize) -> RedisResult<Vec<(String, i64)>> {
+        let mut counters = self.counters.lock().unwrap();
+        let Some(members) = counters.tracked.get_mut(set_key) else {
+            return Ok(vec![]);
+        };
+
+        let popped: Vec<String> = members.iter().take(limit).cloned().collect();
+        for member in &popped {
+            members.remove(member);
+        }
+
+        Ok(popped
+            .into_iter()
+            .filter_map(|member| {
+                let count = counters.values.remove(&format!("{}{}", key_prefix, member))?;
+                Some((member, count))
+            })
+            .collect())
+    }
+
+    async fn zincr(&self, key: &str, member: &str, ttl: Duration) -> RedisResult<()> {
+        let mut counters = self.counters.lock().unwrap();
+        let now = Instant::now();
+        counters.sorted_sets.retain(|_, set| set.expires_at > now);
+
+        let set = counters.sorted_sets.entry(key.to_string()).or_insert_with(|| SortedSet {
+            scores: HashMap::new(),
+            expires_at: now,
+        });
+        *set.scores.entry(member.to_string()).or_default() += 1;
+        set.expires_at = now + ttl;
+        Ok(())
+    }
+
+    /// Always recomputes: a union over in-process maps is cheap enough that
+    /// memoising it under `dest` isn't worth the staleness.
+    async fn zunion_top(
+        &self,
+        _dest: &str,
+        keys: &[String],
+        _ttl: Duration,
+        limit: usize,
+    ) -> RedisResult<Vec<(String, i64)>> {
+        let counters = self.counters.lock().unwrap();
+        let now = Instant::now();
+
+        let mut union: HashMap<&str, i64> = HashMap::new();
+        for set in keys
+            .iter()
+            .filter_map(|key| counters.sorted_sets.get(key))
+            .filter(|set| set.expires_at > now)
+        {
+            for (member, score) in &set.scores {
+                *union.entry(member).or_default() += score;
+            }
+        }
+
+        let mut ranked: Vec<(String, i64)> = union
+            .into_iter()
+            .map(|(member, score)| (member.to_string(), score))
+            .collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.cmp(&a.0)));
+        ranked.truncate(limit);
+        Ok(ranked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> MemoryCache {
+        MemoryCache::new(100, Duration::from_secs(60))
+    }
+
+    #[tokio::test]
+    async fn test_values_and_tombstones() {
+        let cache = cache();
+        cache.set("abc", "https://example.com", Duration::from_secs(30)).await.unwrap();
+        cache.set_negative("gone", Duration::from_secs(30)).await.unwrap();
+
+        assert_eq!(cache.get("abc").await.unwrap().as_deref(), Some("https://example.com"));
+        assert_eq!(cache.lookup("gone").await.unwrap(), Some(CacheEntry::Absent));
+        assert_eq!(cache.lookup("missing").await.unwrap(), None);
+
+        cache.delete("abc").await.unwrap();
+        assert_eq!(cache.get("abc").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_incr_with_ttl_expires() {
+        let cache = cache();
+        let ttl = Duration::from_millis(20);
+        assert_eq!(cache.incr_with_ttl("attempts", 1, ttl).await.unwrap(), 1);
+        assert_eq!(cache.incr_with_ttl("attempts", 1, ttl).await.unwrap(), 2);
+        assert_eq!(cache.incr_with_ttl("attempts", 0, ttl).await.unwrap(), 2);
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(cache.incr_with_ttl("attempts", 1, ttl).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_countdown_seeds_once_and_resets_on_delete() {
+        let cache = cache();
+        let ttl = Duration::from_secs(60);
+        assert_eq!(cache.countdown("left", 2, ttl).await.unwrap(), 1);
+        assert_eq!(cache.countdown("left", 2, ttl).await.unwrap(), 0);
+        assert_eq!(cache.countdown("left", 2, ttl).await.unwrap(), -1);
+
+        cache.delete("left").await.unwrap();
+        assert_eq!(cache.countdown("left", 2, ttl).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_drain_tracked_takes_counters_once() {
+        let cache = cache();
+        cache.incr_tracked("clicks:a", "pending", "a", 2).await.unwrap();
+        cache.incr_tracked("clicks:a", "pending", "a", 1).await.unwrap();
+        cache.incr_tracked("clicks:b", "pending", "b", 1).await.unwrap();
+
+        let mut drained = cache.drain_tracked("pending", "clicks:", 10).await.unwrap();
+        drained.sort();
+        assert_eq!(drained, vec![("a".to_string(), 3), ("b".to_string(), 1)]);
+        assert!(cache.drain_tracked("pending", "clicks:", 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_drain_tracked_respects_limit() {
+        let cache = cache();
+        for member in ["a", "b", "c"] {
+            cache.incr_tracked(&format!("clicks:{}", member), "pending", member, 1).await.unwrap();
+        }
+
+        assert_eq!(cache.drain_tracked("pending", "clicks:", 2).await.unwrap().len(), 2);
+        assert_eq!(cache.drain_tracked("pending", "clicks:", 2).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_zunion_top_sums_across_keys() {
+        let cache = cache();
+        let ttl = Duration::from_secs(60);
+        cache.zincr("day1", "a", ttl).await.unwrap();
+        cache.zincr("day1", "b", ttl).await.unwrap();
+        cache.zincr("day2", "b", ttl).await.unwrap();
+        cache.zincr("day3", "c", ttl).await.unwrap();
+
+        let keys = vec!["day1".to_string(), "day2".to_string()];
+        let top = cache.zunion_top("union", &keys, ttl, 1).await.unwrap();
+        assert_eq!(top, vec![("b".to_string(), 2)]);
+    }
+}
@@ -0,0 +1,318 @@
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, RedisResult, Pipeline, Script, cmd};
+use std::time::Duration;
+
+use crate::services::cache::{CacheEntry, CacheStore, CACHE_HITS, CACHE_MISSES, NEGATIVE_ENTRY};
+use crate::services::local_cache::LocalCache;
+
+static CACHE_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("cache_errors_total", "Failed Redis operations by operation", &["op"])
+        .expect("Failed to register prometheus counter")
+});
+
+/// Pops up to `ARGV[1]` members from `KEYS[1]` and atomically reads and deletes
+/// their counters at `ARGV[2] .. member`, returning a flat `[member, count, ...]` list.
+static DRAIN_SCRIPT: Lazy<Script> = Lazy::new(|| {
+    Script::new(
+        r#"
+        local members = redis.call('SPOP', KEYS[1], ARGV[1])
+        local drained = {}
+        for _, member in ipairs(members) do
+            local key = ARGV[2] .. member
+            local count = redis.call('GET', key)
+            if count then
+                redis.call('DEL', key)
+                table.insert(drained, member)
+                table.insert(drained, count)
+            end
+        end
+        return drained
+        "#,
+    )
+});
+
+/// Seeds `KEYS[1]` with `ARGV[1]` (expiring after `ARGV[2]` ms) if it is
+/// missing, then decrements it.
+static COUNTDOWN_SCRIPT: Lazy<Script> = Lazy::new(|| {
+    Script::new(
+        r#"
+        redis.call('SET', KEYS[1], ARGV[1], 'PX', ARGV[2], 'NX')
+        return redis.call('DECR', KEYS[1])
+        "#,
+    )
+});
+
+/// Two-tier cache: a bounded in-process L1 in front of Redis. Only plain
+/// key/value lookups go through L1; counters and sorted sets are Redis-only.
+///
+/// `ConnectionManager` multiplexes a single connection and is cheap to clone,
+/// so every call works on its own handle and no lock is needed. It reconnects
+/// on its own after an outage; until then every failure is logged and counted
+/// here and callers carry on without the cache.
+pub struct RedisCache {
+    conn: ConnectionManager,
+    local: LocalCache,
+}
+
+impl RedisCache {
+    pub async fn new(redis_url: &str, local: LocalCache) -> Result<Self, redis::RedisError> {
+        let client = redis::Client::open(redis_url)?;
+        let conn = ConnectionManager::new(client).await?;
+
+        let cache = Self { conn, local };
+        Ok(cache)
+    }
+
+    fn conn(&self) -> ConnectionManager {
+        self.conn.clone()
+    }
+
+    pub async fn incr(&self, key: &str) -> RedisResult<i64> {
+        observe("incr", self.conn().incr(key, 1).await)
+    }
+
+    pub async fn batch_get(&self, keys: &[String]) -> RedisResult<Vec<Option<String>>> {
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+        let values: Vec<Option<String>> = observe("batch_get", self.conn().get(keys).await)?;
+        Ok(values
+            .into_iter()
+            .map(|value| value.filter(|value| value != NEGATIVE_ENTRY))
+            .collect())
+    }
+
+    pub async fn batch_set(&self, key_values: &[(String, String)], ttl: Duration) -> RedisResult<()> {
+        if key_values.is_empty() {
+            return Ok(());
+        }
+
+        let mut pipe = Pipeline::new();
+        for (key, value) in key_values {
+            pipe.pset_ex(key, value, ttl_millis(ttl)).ignore();
+        }
+        observe("batch_set", pipe.query_async::<()>(&mut self.conn()).await)?;
+
+        for (key, value) in key_values {
+            self.local.insert(key, value, ttl);
+        }
+        Ok(())
+    }
+
+    pub async fn exists(&self, key: &str) -> RedisResult<bool> {
+        observe("exists", self.conn().exists(key).await)
+    }
+
+    pub async fn get_connection_info(&self) -> RedisResult<String> {
+        observe("info", cmd("INFO").arg("clients").query_async(&mut self.conn()).await)
+    }
+}
+
+#[async_trait]
+impl CacheStore for RedisCache {
+    /// Reports the remaining Redis TTL (`None` for L1 hits and keys without one).
+    async fn lookup(&self, key: &str) -> RedisResult<Option<CacheEntry>> {
+        if let Some(value) = self.local.get(key) {
+            CACHE_HITS.with_label_values(&["l1"]).inc();
+            return Ok(Some(CacheEntry::Present { value, ttl: None }));
+        }
+        CACHE_MISSES.with_label_values(&["l1"]).inc();
+
+        let (value, ttl_ms): (Option<String>, i64) = observe(
+            "lookup",
+            redis::pipe()
+                .get(key)
+                .pttl(key)
+                .query_async(&mut self.conn())
+                .await,
+        )?;
+
+        let Some(value) = value else {
+            CACHE_MISSES.with_label_values(&["redis"]).inc();
+            return Ok(None);
+        };
+
+        if value == NEGATIVE_ENTRY {
+            CACHE_HITS.with_label_values(&["negative"]).inc();
+            return Ok(Some(CacheEntry::Absent));
+        }
+
+        CACHE_HITS.with_label_values(&["redis"]).inc();
+        // PTTL is -1 for keys without an expiry; L1 then applies its own cap.
+        let ttl = u64::try_from(ttl_ms).ok().map(Duration::from_millis);
+        self.local.insert(key, &value, ttl.unwrap_or(Duration::MAX));
+
+        Ok(Some(CacheEntry::Present { value, ttl }))
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> RedisResult<()> {
+        observe("set", self.conn().pset_ex::<_, _, ()>(key, value, ttl_millis(ttl)).await)?;
+        self.local.insert(key, value, ttl);
+        Ok(())
+    }
+
+    /// Tombstones live in Redis only, so a later `set` or `delete` on any
+    /// instance clears them everywhere at once.
+    async fn set_negative(&self, key: &str, ttl: Duration) -> RedisResult<()> {
+        if ttl.is_zero() {
+            return Ok(());
+        }
+        self.local.invalidate(key);
+        observe("set_negative", self.conn().set_ex(key, NEGATIVE_ENTRY, ttl.as_secs().max(1)).await)
+    }
+
+    async fn delete(&self, key: &str) -> RedisResult<()> {
+        self.local.invalidate(key);
+        observe("delete", self.conn().del(key).await)
+    }
+
+    async fn incr_with_ttl(&self, key: &str, by: i64, ttl: Duration) -> RedisResult<i64> {
+        let mut pipe = Pipeline::new();
+        pipe.atomic()
+            .incr(key, by)
+            .expire(key, ttl.as_secs().max(1) as i64).ignore();
+        let (count,): (i64,) = observe("incr_with_ttl", pipe.query_async(&mut self.conn()).await)?;
+        Ok(count)
+    }
+
+    async fn countdown(&self, key: &str, initial: i64, ttl: Duration) -> RedisResult<i64> {
+        let mut invocation = COUNTDOWN_SCRIPT.prepare_invoke();
+        invocation.key(key).arg(initial).arg(ttl_millis(ttl));
+        observe("countdown", invocation.invoke_async(&mut self.conn()).await)
+    }
+
+    async fn incr_tracked(&self, key: &str, set_key: &str, member: &str, by: i64) -> RedisResult<()> {
+        let mut pipe = Pipeline::new();
+        pipe.atomic()
+            .incr(key, by).ignore()
+            .sadd(set_key, member).ignore();
+        observe("incr_tracked", pipe.query_async(&mut self.conn()).await)
+    }
+
+    async fn drain_tracked(&self, set_key: &str, key_prefix: &str, limit: usize) -> RedisResult<Vec<(String, i64)>> {
+        let mut invocation = DRAIN_SCRIPT.prepare_invoke();
+        invocation.key(set_key).arg(limit).arg(key_prefix);
+        observe("drain_tracked", invocation.invoke_async(&mut self.conn()).await)
+    }
+
+    async fn zincr(&self, key: &str, member: &str, ttl: Duration) -> RedisResult<()> {
+        let mut pipe = Pipeline::new();
+        pipe.zincr(key, member, 1).ignore();
+        pipe.expire(key, ttl.as_secs() as i64).ignore();
+        observe("zincr", pipe.query_async(&mut self.conn()).await)
+    }
+
+    async fn zunion_top(
+        &self,
+        dest: &str,
+        keys: &[String],
+        ttl: Duration,
+        limit: usize,
+    ) -> RedisResult<Vec<(String, i64)>> {
+        if limit == 0 || keys.is_empty() {
+            return Ok(vec![]);
+        }
+
+        if !self.exists(dest).await? {
+            let mut pipe = Pipeline::new();
+            pipe.atomic()
+                .zunionstore(dest, keys).ignore()
+                .expire(dest, ttl.as_secs() as i64).ignore();
+            observe("zunion_top", pipe.query_async::<()>(&mut self.conn()).await)?;
+        }
+
+        observe("zunion_top", self.conn().zrevrange_withscores(dest, 0, limit as isize - 1).await)
+    }
+}
+
+/// Counts and logs a failed command before handing the result back. Redis is
+/// only a cache, so callers fall back to the database rather than failing the
+/// request; this keeps an outage visible.
+fn observe<T>(op: &str, result: RedisResult<T>) -> RedisResult<T> {
+    if let Err(e) = &result {
+        CACHE_ERRORS.with_label_values(&[op]).inc();
+        tracing::warn!("Redis {} failed: {}", op, e);
+    }
+    result
+}
+
+/// Redis rejects a zero expiry, so sub-millisecond TTLs round up.
+fn ttl_millis(ttl: Duration) -> u64 {
+    (ttl.as_millis() as u64).max(1)
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::useless_vec)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ttl_millis_rounds_up() {
+        assert_eq!(ttl_millis(Duration::from_micros(10)), 1);
+        assert_eq!(ttl_millis(Duration::from_secs(60)), 60_000);
+    }
+
+    #[test]
+    fn test_batch_get_empty_keys() {
+        let keys: Vec<String> = vec![];
+        assert_eq!(keys.is_empty(), true);
+    }
+
+    #[test]
+    fn test_batch_set_empty_keys() {
+        let key_values: Vec<(String, String)> = vec![];
+        assert_eq!(key_values.is_empty(), true);
+    }
+
+    #[test]
+    fn test_ttl_conversion() {
+        let ttl = Duration::from_secs(60);
+        assert_eq!(ttl.as_secs(), 60);
+
+        let default_ttl = Duration::from_secs(86400);
+        assert_eq!(default_ttl.as_secs(), 86400);
+    }
+
+    #[test]
+    fn test_redis_url_parsing() {
+        let redis_url = "redis://127.0.0.1:6379";
+        assert!(redis_url.starts_with("redis://"));
+        assert!(redis_url.contains("6379"));
+    }
+
+    #[test]
+    fn test_key_value_pair_construction() {
+        let key = "test_key";
+        let value = "test_value";
+        let pair = (key.to_string(), value.to_string());
+
+        assert_eq!(pair.0, "test_key");
+        assert_eq!(pair.1, "test_value");
+    }
+
+    #[test]
+    fn test_pipeline_key_value_operations() {
+        let key_values = vec![
+            ("key1".to_string(), "value1".to_string()),
+            ("key2".to_string(), "value2".to_string()),
+        ];
+
+        assert_eq!(key_values.len(), 2);
+        assert_eq!(key_values[0].0, "key1");
+        assert_eq!(key_values[1].1, "value2");
+    }
+
+    #[test]
+    fn test_redis_command_construction() {
+        let key = "test_key";
+        let value = "test_value";
+        let ttl_secs = 3600u64;
+
+        assert!(!key.is_empty());
+        assert!(!value.is_empty());
+        assert!(ttl_secs > 0);
+    }
+}
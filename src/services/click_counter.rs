@@ -0,0 +1,202 @@
+use once_cell::sync::Lazy;
+use prometheus::{register_counter, register_histogram, Counter, Histogram};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::error::AppError;
+use crate::services::{cache::CacheStore, db::LinkStore};
+
+const PENDING_SET_KEY: &str = "pending:clicks";
+const COUNTER_KEY_PREFIX: &str = "clicks:";
+const REMAINING_KEY_PREFIX: &str = "clicks_left:";
+
+/// How long a click-limit countdown lives in the cache before it is reseeded.
+const COUNTDOWN_TTL: Duration = Duration::from_secs(86400);
+
+static FLUSHED_CLICKS: Lazy<Counter> = Lazy::new(|| {
+    register_counter!("click_flush_clicks_total", "Clicks written from Redis to Postgres")
+        .expect("Failed to register prometheus counter")
+});
+
+static FLUSH_ERRORS: Lazy<Counter> = Lazy::new(|| {
+    register_counter!("click_flush_errors_total", "Failed click counter flushes")
+        .expect("Failed to register prometheus counter")
+});
+
+static FLUSH_DURATION: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!("click_flush_duration_seconds", "Time spent flushing click counters")
+        .expect("Failed to register prometheus histogram")
+});
+
+pub fn counter_key(short_code: &str) -> String {
+    format!("{}{}", COUNTER_KEY_PREFIX, short_code)
+}
+
+/// Buffers one click in the cache until the next flush.
+pub async fn record_click(cache: &dyn CacheStore, short_code: &str) -> redis::RedisResult<()> {
+    cache.incr_tracked(&counter_key(short_code), PENDING_SET_KEY, short_code, 1).await
+}
+
+pub fn remaining_key(short_code: &str) -> String {
+    format!("{}{}", REMAINING_KEY_PREFIX, short_code)
+}
+
+/// Counts one click on a link limited to `max_clicks`, returning `false` once
+/// the limit is used up. These clicks bypass the write-behind buffer: the
+/// database makes the final call with an atomic conditional update. The cache
+/// countdown in front of it only turns away clicks on exhausted links; it is
+/// seeded with the full limit, so a lost or reseeded countdown can let extra
+/// requests through to the database but never refuses a valid one.
+pub async fn claim_limited_click(
+    cache: &dyn CacheStore,
+    db: &dyn LinkStore,
+    short_code: &str,
+    max_clicks: i64,
+) -> Result<bool, AppError> {
+    if let Ok(remaining) = cache.countdown(&remaining_key(short_code), max_clicks, COUNTDOWN_TTL).await {
+        if remaining < 0 {
+            return Ok(false);
+        }
+    }
+
+    db.consume_click(short_code).await
+}
+
+/// Write-behind flusher that drains buffered click counters into `links.clicks`.
+pub struct ClickFlusher {
+    db: Arc<dyn LinkStore>,
+    cache: Arc<dyn CacheStore>,
+    interval: Duration,
+    batch_size: usize,
+}
+
+impl ClickFlusher {
+    pub fn new(
+        db: Arc<dyn LinkStore>,
+        cache: Arc<dyn CacheStore>,
+        interval: Duration,
+        batch_size: usize,
+    ) -> Self {
+        Self {
+            db,
+            cache,
+            interval,
+            batch_size: batch_size.max(1),
+        }
+    }
+
+    /// Flushes on every tick until `shutdown` flips to `true`, then performs a
+    /// final flush so buffered clicks survive a graceful restart.
+    pub async fn run(self, mut shutdown: watch::Receiver<bool>) {
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.changed() => break,
+            }
+
+            if *shutdown.borrow() {
+                break;
+            }
+
+            self.flush_all().await;
+        }
+
+        let flushed = self.flush_all().await;
+        tracing::info!("Flushed {} buffered clicks on shutdown", flushed);
+    }
+
+    /// Drains batches until the pending set is empty or a flush fails.
+    pub async fn flush_all(&self) -> u64 {
+        let mut total = 0;
+        loop {
+            match self.flush_batch().await {
+                Ok((drained, clicks)) => {
+                    total += clicks;
+                    if drained < self.batch_size {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    FLUSH_ERRORS.inc();
+                    tracing::error!("Failed to flush click counters: {}", e);
+                    break;
+                }
+            }
+        }
+        total
+    }
+
+    async fn flush_batch(&self) -> Result<(usize, u64), AppError> {
+        let timer = FLUSH_DURATION.start_timer();
+
+        let drained: Vec<(String, i64)> = self
+            .cache
+            .drain_tracked(PENDING_SET_KEY, COUNTER_KEY_PREFIX, self.batch_size)
+            .await?;
+
+        if drained.is_empty() {
+            timer.observe_duration();
+            return Ok((0, 0));
+        }
+
+        let (codes, counts): (Vec<String>, Vec<i64>) = drained.iter().cloned().unzip();
+
+        if let Err(e) = self.db.add_clicks(&codes, &counts).await {
+            self.restore(&drained).await;
+            return Err(e);
+        }
+
+        let clicks: i64 = counts.iter().sum();
+        FLUSHED_CLICKS.inc_by(clicks as f64);
+        timer.observe_duration();
+
+        Ok((drained.len(), clicks as u64))
+    }
+
+    /// Puts counts back after a failed database write so the next tick retries them.
+    async fn restore(&self, drained: &[(String, i64)]) {
+        for (code, count) in drained {
+            if let Err(e) = self.cache.incr_tracked(&counter_key(code), PENDING_SET_KEY, code, *count).await {
+                tracing::error!("Lost {} buffered clicks for {}: {}", count, code, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::link::Link;
+    use crate::services::{cache::MemoryCache, db::MemoryStore};
+
+    #[test]
+    fn test_counter_key_format() {
+        assert_eq!(counter_key("abc123"), "clicks:abc123");
+    }
+
+    #[tokio::test]
+    async fn test_claim_limited_click_never_exceeds_limit() {
+        let cache = MemoryCache::new(100, Duration::from_secs(60));
+        let db = MemoryStore::new();
+        db.create_link(&Link { max_clicks: Some(2), ..Link::for_url("once", "https://example.com") })
+        .await
+        .unwrap();
+
+        let mut claimed = 0;
+        for _ in 0..4 {
+            if claim_limited_click(&cache, &db, "once", 2).await.unwrap() {
+                claimed += 1;
+            }
+        }
+        assert_eq!(claimed, 2);
+
+        // A reseeded countdown still can't push the link past its limit.
+        cache.delete(&remaining_key("once")).await.unwrap();
+        assert!(!claim_limited_click(&cache, &db, "once", 2).await.unwrap());
+        assert_eq!(db.find_link_by_code("once").await.unwrap().unwrap().clicks, 2);
+    }
+}
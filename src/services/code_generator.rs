@@ -0,0 +1,297 @@
+use rand::{rngs::OsRng, Rng};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::services::shortener::generate_short_code_salted;
+
+/// Inputs available to a generator for one attempt at producing a code.
+pub struct CodeRequest<'a> {
+    pub url: &'a str,
+    /// Zero for the first try, incremented after every collision.
+    pub attempt: u32,
+    /// Fresh value from the shared sequence, present when the generator asks
+    /// for one through `uses_sequence`.
+    pub sequence: Option<u64>,
+}
+
+pub trait CodeGenerator: Send + Sync {
+    fn generate(&self, request: &CodeRequest) -> String;
+
+    fn uses_sequence(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeStrategy {
+    Hash,
+    Random,
+    Sequential,
+    Timestamp,
+}
+
+impl FromStr for CodeStrategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "hash" => Ok(Self::Hash),
+            "random" => Ok(Self::Random),
+            "sequential" => Ok(Self::Sequential),
+            "timestamp" => Ok(Self::Timestamp),
+            other => Err(format!("unknown short code strategy: {}", other)),
+        }
+    }
+}
+
+impl CodeStrategy {
+    pub fn build(self, alphabet: &str, length: usize, secret: &str) -> Arc<dyn CodeGenerator> {
+        let alphabet = alphabet.as_bytes().to_vec();
+        match self {
+            Self::Hash => Arc::new(HashCodeGenerator { alphabet, length }),
+            Self::Random => Arc::new(RandomCodeGenerator { alphabet, length }),
+            Self::Sequential => Arc::new(SequentialCodeGenerator::new(&alphabet, length, secret)),
+            Self::Timestamp => Arc::new(TimestampCodeGenerator { alphabet, length }),
+        }
+    }
+}
+
+/// Derives the code from the URL, so the first attempt for a given URL is
+/// always the same code; retries mix in the attempt number.
+pub struct HashCodeGenerator {
+    alphabet: Vec<u8>,
+    length: usize,
+}
+
+impl CodeGenerator for HashCodeGenerator {
+    fn generate(&self, request: &CodeRequest) -> String {
+        generate_short_code_salted(request.url, request.attempt as u64, &self.alphabet, self.length)
+    }
+}
+
+/// Draws every character uniformly from the OS random number generator.
+pub struct RandomCodeGenerator {
+    alphabet: Vec<u8>,
+    length: usize,
+}
+
+impl CodeGenerator for RandomCodeGenerator {
+    fn generate(&self, _request: &CodeRequest) -> String {
+        (0..self.length)
+            .map(|_| self.alphabet[OsRng.gen_range(0..self.alphabet.len())] as char)
+            .collect()
+    }
+}
+
+/// Hashes the URL together with the current time in nanoseconds, so the same
+/// URL shortened twice gets two codes.
+pub struct TimestampCodeGenerator {
+    alphabet: Vec<u8>,
+    length: usize,
+}
+
+impl CodeGenerator for TimestampCodeGenerator {
+    fn generate(&self, request: &CodeRequest) -> String {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+        generate_short_code_salted(request.url, timestamp ^ request.attempt as u64, &self.alphabet, self.length)
+    }
+}
+
+/// Encodes a database sequence value, Sqids/Hashids style: the value is passed
+/// through a keyed permutation and written in an alphabet shuffled by the same
+/// secret. Codes are short and never collide with each other, but consecutive
+/// links do not get guessable neighbouring codes.
+pub struct SequentialCodeGenerator {
+    alphabet: Vec<u8>,
+    length: usize,
+    key: [u8; 32],
+    bits: u32,
+}
+
+const FEISTEL_ROUNDS: u8 = 4;
+
+/// Shortest `SHORT_CODE_SECRET` accepted for the sequential strategy; anyone
+/// who knows the secret can decode codes back to row IDs.
+pub const MIN_SECRET_LEN: usize = 16;
+
+impl SequentialCodeGenerator {
+    pub fn new(alphabet: &[u8], length: usize, secret: &str) -> Self {
+        let key: [u8; 32] = Sha256::digest(secret.as_bytes()).into();
+
+        // Largest even bit width whose values all fit in `length` characters.
+        let capacity = (length as f64 * (alphabet.len() as f64).log2()).floor() as u32;
+        let bits = (capacity.min(64) / 2) * 2;
+
+        Self {
+            alphabet: shuffle_alphabet(alphabet, &key),
+            length,
+            key,
+            bits,
+        }
+    }
+
+    fn encode(&self, mut value: u64) -> String {
+        let base = self.alphabet.len() as u64;
+        let mut code = Vec::with_capacity(self.length);
+        while value > 0 || code.len() < self.length {
+            code.push(self.alphabet[(value % base) as usize]);
+            value /= base;
+        }
+        code.reverse();
+        String::from_utf8(code).expect("alphabet is ASCII")
+    }
+
+    /// Balanced Feistel network over `bits` bits: a bijection, so distinct
+    /// sequence values always yield distinct codes. Values beyond the domain
+    /// fall back to the full 64-bit permutation and produce longer codes.
+    fn permute(&self, value: u64) -> u64 {
+        let bits = if self.bits > 0 && value < (1u64 << self.bits) { self.bits } else { 64 };
+        let half = bits / 2;
+        let mask = if half == 32 { u32::MAX as u64 } else { (1u64 << half) - 1 };
+
+        let mut left = (value >> half) & mask;
+        let mut right = value & mask;
+        for round in 0..FEISTEL_ROUNDS {
+            let next = left ^ (self.round_function(right, round) & mask);
+            left = right;
+            right = next;
+        }
+
+        (left << half) | right
+    }
+
+    fn round_function(&self, value: u64, round: u8) -> u64 {
+        let mut hasher = Sha256::new();
+        hasher.update(self.key);
+        hasher.update([round]);
+        hasher.update(value.to_be_bytes());
+        let result = hasher.finalize();
+        u64::from_be_bytes(result[..8].try_into().unwrap())
+    }
+}
+
+impl CodeGenerator for SequentialCodeGenerator {
+    fn generate(&self, request: &CodeRequest) -> String {
+        let sequence = request
+            .sequence
+            .expect("sequential generator requires a sequence value");
+        self.encode(self.permute(sequence))
+    }
+
+    fn uses_sequence(&self) -> bool {
+        true
+    }
+}
+
+/// Deterministic Fisher-Yates shuffle driven by `key`.
+fn shuffle_alphabet(alphabet: &[u8], key: &[u8; 32]) -> Vec<u8> {
+    let mut shuffled = alphabet.to_vec();
+    for i in (1..shuffled.len()).rev() {
+        let mut hasher = Sha256::new();
+        hasher.update(key);
+        hasher.update((i as u64).to_be_bytes());
+        let result = hasher.finalize();
+        let j = (u64::from_be_bytes(result[..8].try_into().unwrap()) % (i as u64 + 1)) as usize;
+        shuffled.swap(i, j);
+    }
+    shuffled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::shortener::DEFAULT_ALPHABET;
+    use std::collections::HashSet;
+
+    fn request(attempt: u32, sequence: Option<u64>) -> CodeRequest<'static> {
+        CodeRequest {
+            url: "https://example.com",
+            attempt,
+            sequence,
+        }
+    }
+
+    #[test]
+    fn test_strategy_from_str() {
+        assert_eq!("hash".parse(), Ok(CodeStrategy::Hash));
+        assert_eq!("Random".parse(), Ok(CodeStrategy::Random));
+        assert_eq!("sequential".parse(), Ok(CodeStrategy::Sequential));
+        assert_eq!("timestamp".parse(), Ok(CodeStrategy::Timestamp));
+        assert!("md5".parse::<CodeStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_hash_generator_is_deterministic_per_attempt() {
+        let generator = CodeStrategy::Hash.build(DEFAULT_ALPHABET, 7, "");
+
+        assert_eq!(generator.generate(&request(0, None)), generator.generate(&request(0, None)));
+        assert_ne!(generator.generate(&request(0, None)), generator.generate(&request(1, None)));
+        assert!(!generator.uses_sequence());
+    }
+
+    #[test]
+    fn test_random_generator_respects_length_and_alphabet() {
+        let generator = CodeStrategy::Random.build("abc", 12, "");
+        let code = generator.generate(&request(0, None));
+
+        assert_eq!(code.len(), 12);
+        assert!(code.chars().all(|c| "abc".contains(c)));
+        assert_ne!(code, generator.generate(&request(0, None)));
+    }
+
+    #[test]
+    fn test_sequential_generator_codes_are_unique_and_fixed_length() {
+        let generator = CodeStrategy::Sequential.build(DEFAULT_ALPHABET, 7, "secret");
+        assert!(generator.uses_sequence());
+
+        let codes: HashSet<String> = (0..10_000)
+            .map(|sequence| generator.generate(&request(0, Some(sequence))))
+            .collect();
+
+        assert_eq!(codes.len(), 10_000);
+        assert!(codes.iter().all(|code| code.len() == 7));
+    }
+
+    #[test]
+    fn test_sequential_generator_depends_on_secret() {
+        let first = CodeStrategy::Sequential.build(DEFAULT_ALPHABET, 7, "one");
+        let second = CodeStrategy::Sequential.build(DEFAULT_ALPHABET, 7, "two");
+
+        assert_ne!(first.generate(&request(0, Some(1))), second.generate(&request(0, Some(1))));
+    }
+
+    #[test]
+    fn test_sequential_generator_handles_values_beyond_domain() {
+        let generator = SequentialCodeGenerator::new(DEFAULT_ALPHABET.as_bytes(), 4, "secret");
+        let code = generator.generate(&request(0, Some(u64::MAX)));
+
+        assert!(code.len() > 4);
+    }
+
+    #[test]
+    fn test_permute_is_a_bijection() {
+        let generator = SequentialCodeGenerator::new(b"01", 8, "secret");
+        let permuted: HashSet<u64> = (0..256).map(|value| generator.permute(value)).collect();
+
+        assert_eq!(generator.bits, 8);
+        assert_eq!(permuted.len(), 256);
+        assert!(permuted.iter().all(|value| *value < 256));
+    }
+
+    #[test]
+    fn test_shuffle_alphabet_keeps_characters() {
+        let key: [u8; 32] = Sha256::digest(b"secret").into();
+        let mut shuffled = shuffle_alphabet(DEFAULT_ALPHABET.as_bytes(), &key);
+
+        assert_ne!(shuffled, DEFAULT_ALPHABET.as_bytes());
+        shuffled.sort_unstable();
+        assert_eq!(shuffled, DEFAULT_ALPHABET.as_bytes());
+    }
+}
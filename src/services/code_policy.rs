@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+
+/// First path segments the router serves itself. A short code equal to one of
+/// these would be shadowed by (or shadow) a real route.
+pub const ROUTE_SEGMENTS: &[&str] = &["api", "health", "metrics"];
+
+/// Names that are commonly requested by browsers and crawlers or that read as
+/// part of the service, kept out of circulation alongside the route segments.
+const DEFAULT_RESERVED: &[&str] = &[
+    "admin", "assets", "favicon.ico", "login", "logout", "robots.txt", "static", "status",
+];
+
+/// Substrings that must not appear in a code, matched after folding case and
+/// common digit-for-letter substitutions. Deliberately short: every entry also
+/// rejects innocent codes that contain it.
+const DEFAULT_BLOCKLIST: &[&str] = &[
+    "bitch", "boob", "cunt", "dick", "dildo", "fag", "fuck", "jizz", "kike", "nazi", "nigg",
+    "penis", "piss", "porn", "pussy", "shit", "slut", "tits", "twat", "wank", "whore",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeRejection {
+    Reserved,
+    Blocked,
+}
+
+/// Decides which short codes may be handed out, for both user-chosen and
+/// generated codes.
+pub struct CodePolicy {
+    reserved: HashSet<String>,
+    blocklist: Vec<String>,
+}
+
+impl CodePolicy {
+    pub fn new<R, B>(extra_reserved: R, extra_blocked: B) -> Self
+    where
+        R: IntoIterator,
+        R::Item: AsRef<str>,
+        B: IntoIterator,
+        B::Item: AsRef<str>,
+    {
+        let reserved = ROUTE_SEGMENTS
+            .iter()
+            .chain(DEFAULT_RESERVED)
+            .map(|word| word.to_string())
+            .chain(extra_reserved.into_iter().map(|word| word.as_ref().to_string()))
+            .map(|word| word.trim().to_ascii_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        let blocklist = DEFAULT_BLOCKLIST
+            .iter()
+            .map(|word| word.to_string())
+            .chain(extra_blocked.into_iter().map(|word| word.as_ref().to_string()))
+            .map(|word| fold(word.trim()))
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        Self { reserved, blocklist }
+    }
+
+    pub fn check(&self, code: &str) -> Result<(), CodeRejection> {
+        if self.reserved.contains(&code.to_ascii_lowercase()) {
+            return Err(CodeRejection::Reserved);
+        }
+
+        let folded = fold(code);
+        if self.blocklist.iter().any(|word| folded.contains(word.as_str())) {
+            return Err(CodeRejection::Blocked);
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for CodePolicy {
+    fn default() -> Self {
+        Self::new(std::iter::empty::<&str>(), std::iter::empty::<&str>())
+    }
+}
+
+/// Lowercases `code`, maps look-alike digits and symbols to the letters they
+/// stand in for, and drops separators, so `Sh1t`, `s_h_i_t` and `SHIT` compare
+/// equal.
+fn fold(code: &str) -> String {
+    code.chars()
+        .filter(|c| !matches!(c, '_' | '-' | '.'))
+        .map(|c| match c.to_ascii_lowercase() {
+            '0' => 'o',
+            '1' | '!' => 'i',
+            '3' => 'e',
+            '4' | '@' => 'a',
+            '5' | '$' => 's',
+            '7' => 't',
+            '8' => 'b',
+            '9' => 'g',
+            other => other,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("api")]
+    #[case("API")]
+    #[case("health")]
+    #[case("metrics")]
+    #[case("robots.txt")]
+    fn test_route_and_default_words_are_reserved(#[case] code: &str) {
+        assert_eq!(CodePolicy::default().check(code), Err(CodeRejection::Reserved));
+    }
+
+    #[rstest]
+    #[case("shit")]
+    #[case("Sh1t")]
+    #[case("xx5h1txx")]
+    #[case("f-u-c-k")]
+    fn test_blocked_words_are_rejected_with_substitutions(#[case] code: &str) {
+        assert_eq!(CodePolicy::default().check(code), Err(CodeRejection::Blocked));
+    }
+
+    #[rstest]
+    #[case("abc123")]
+    #[case("apis")]
+    #[case("my-link")]
+    fn test_ordinary_codes_are_allowed(#[case] code: &str) {
+        assert_eq!(CodePolicy::default().check(code), Ok(()));
+    }
+
+    #[test]
+    fn test_configured_extras() {
+        let policy = CodePolicy::new(["Pricing", " "], ["acme"]);
+
+        assert_eq!(policy.check("pricing"), Err(CodeRejection::Reserved));
+        assert_eq!(policy.check("x4cmex"), Err(CodeRejection::Blocked));
+        assert_eq!(policy.check("pricing2"), Ok(()));
+    }
+}
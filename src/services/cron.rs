@@ -0,0 +1,213 @@
+use std::str::FromStr;
+use time::OffsetDateTime;
+
+/// A standard five-field cron expression: minute, hour, day of month, month
+/// and day of week. Fields take `*`, values, `a-b` ranges and `/step`, joined
+/// by commas; months and weekdays also take three-letter names, and Sunday is
+/// either 0 or 7. As in Vixie cron, when both day fields are restricted a day
+/// matching either one counts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+struct Field {
+    name: &'static str,
+    min: u8,
+    max: u8,
+    names: &'static [&'static str],
+}
+
+const MINUTE: Field = Field { name: "minute", min: 0, max: 59, names: &[] };
+const HOUR: Field = Field { name: "hour", min: 0, max: 23, names: &[] };
+const DAY: Field = Field { name: "day of month", min: 1, max: 31, names: &[] };
+const MONTH: Field = Field {
+    name: "month",
+    min: 1,
+    max: 12,
+    names: &["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"],
+};
+const WEEKDAY: Field = Field {
+    name: "day of week",
+    min: 0,
+    max: 7,
+    names: &["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"],
+};
+
+impl CronExpr {
+    /// Whether the wall-clock minute of `local` is one the expression fires
+    /// on; seconds are ignored.
+    pub fn matches(&self, local: OffsetDateTime) -> bool {
+        let day = has(self.days, local.day());
+        let weekday = has(self.weekdays, local.weekday().number_days_from_sunday());
+        let day_matches = if self.any_day || self.any_weekday { day && weekday } else { day || weekday };
+
+        has(self.minutes, local.minute())
+            && has(self.hours, local.hour())
+            && has(self.months, local.month() as u8)
+            && day_matches
+    }
+}
+
+impl FromStr for CronExpr {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("expected 5 fields, found {}", fields.len()));
+        };
+
+        let mut weekdays = parse_field(weekday, &WEEKDAY)?;
+        // 7 is another name for Sunday.
+        if has(weekdays, 7) {
+            weekdays |= 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, &MINUTE)?,
+            hours: parse_field(hour, &HOUR)?,
+            days: parse_field(day, &DAY)?,
+            months: parse_field(month, &MONTH)?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+}
+
+fn has(set: u64, value: u8) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Bit set of the values `text` selects within `field`.
+fn parse_field(text: &str, field: &Field) -> Result<u64, String> {
+    let mut set = 0;
+    for item in text.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => match step.parse::<u8>() {
+                Ok(step) if step > 0 => (range, Some(step)),
+                _ => return Err(format!("invalid step {:?} in {} field", step, field.name)),
+            },
+            None => (item, None),
+        };
+
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (field.min, field.max),
+            Some((start, end)) => (parse_value(start, field)?, parse_value(end, field)?),
+            // `5/15` runs from 5 to the end of the field.
+            None if step.is_some() => (parse_value(range, field)?, field.max),
+            None => {
+                let value = parse_value(range, field)?;
+                (value, value)
+            }
+        };
+        if start > end {
+            return Err(format!("range {:?} in {} field runs backwards", range, field.name));
+        }
+
+        for value in (start..=end).step_by(step.unwrap_or(1).into()) {
+            set |= 1 << value;
+        }
+    }
+
+    Ok(set)
+}
+
+fn parse_value(text: &str, field: &Field) -> Result<u8, String> {
+    let value = match field.names.iter().position(|name| name.eq_ignore_ascii_case(text)) {
+        Some(index) => index as u8 + field.min,
+        None => text.parse().map_err(|_| format!("invalid {} value {:?}", field.name, text))?,
+    };
+
+    if !(field.min..=field.max).contains(&value) {
+        return Err(format!(
+            "{} value {} is outside {}-{}",
+            field.name, value, field.min, field.max
+        ));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn cron(expression: &str) -> CronExpr {
+        expression.parse().unwrap()
+    }
+
+    #[test]
+    fn test_matches_ranges_and_names() {
+        let hours = cron("* 9-17 * * MON-FRI");
+
+        // 2025-07-02 is a Wednesday.
+        assert!(hours.matches(datetime!(2025-07-02 9:00 UTC)));
+        assert!(hours.matches(datetime!(2025-07-02 17:59:59 UTC)));
+        assert!(!hours.matches(datetime!(2025-07-02 18:00 UTC)));
+        assert!(!hours.matches(datetime!(2025-07-05 10:00 UTC)));
+    }
+
+    #[test]
+    fn test_matches_lists_and_steps() {
+        let quarter_hours = cron("*/15 8,12 * jan-mar *");
+
+        assert!(quarter_hours.matches(datetime!(2025-02-10 8:45 UTC)));
+        assert!(quarter_hours.matches(datetime!(2025-02-10 12:00 UTC)));
+        assert!(!quarter_hours.matches(datetime!(2025-02-10 12:10 UTC)));
+        assert!(!quarter_hours.matches(datetime!(2025-04-10 12:00 UTC)));
+
+        let from_five = cron("5/20 * * * *");
+        assert!(from_five.matches(datetime!(2025-02-10 8:45 UTC)));
+        assert!(!from_five.matches(datetime!(2025-02-10 8:40 UTC)));
+    }
+
+    #[test]
+    fn test_sunday_is_zero_or_seven() {
+        // 2025-07-06 is a Sunday.
+        assert!(cron("* * * * 0").matches(datetime!(2025-07-06 10:00 UTC)));
+        assert!(cron("* * * * 7").matches(datetime!(2025-07-06 10:00 UTC)));
+        assert!(cron("* * * * 5-7").matches(datetime!(2025-07-06 10:00 UTC)));
+        assert!(!cron("* * * * 1-6").matches(datetime!(2025-07-06 10:00 UTC)));
+    }
+
+    #[test]
+    fn test_restricted_day_fields_match_either() {
+        let first_or_monday = cron("0 0 1 * MON");
+
+        assert!(first_or_monday.matches(datetime!(2025-07-01 0:00 UTC)));
+        assert!(first_or_monday.matches(datetime!(2025-07-07 0:00 UTC)));
+        assert!(!first_or_monday.matches(datetime!(2025-07-08 0:00 UTC)));
+
+        // With one day field unrestricted, the other must match.
+        assert!(!cron("0 0 */2 * *").matches(datetime!(2025-07-02 0:00 UTC)));
+        assert!(!cron("0 0 1 * */1").matches(datetime!(2025-07-07 0:00 UTC)));
+    }
+
+    #[test]
+    fn test_rejects_malformed_expressions() {
+        for expression in [
+            "",
+            "* * * *",
+            "0 * 9-17 * * *",
+            "* 25 * * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "* 17-9 * * *",
+            "*/0 * * * *",
+            "* * * * FUN",
+            "not a cron",
+        ] {
+            assert!(expression.parse::<CronExpr>().is_err(), "{:?}", expression);
+        }
+    }
+}
@@ -1,73 +1,408 @@
-use sqlx::PgPool;
-use crate::models::link::Link;
+mod memory;
+mod postgres;
+mod sqlite;
+
+use async_trait::async_trait;
+use sqlx::types::time::OffsetDateTime;
+use std::collections::HashMap;
+use std::sync::Arc;
+use url::Url;
+
 use crate::error::AppError;
+use crate::models::analytics::{AnalyticsResponse, ClickEvent, ReferrerClicks, TopLink};
+use crate::models::link::{IdempotencyRecord, Link, RedirectStatus};
+
+pub use memory::MemoryStore;
+pub use postgres::PostgresStore;
+pub use sqlite::SqliteStore;
+
+/// How many rows the per-country and per-referrer breakdowns return.
+const BREAKDOWN_LIMIT: usize = 20;
+
+/// Persistent storage for links, click events and idempotency keys.
+#[async_trait]
+pub trait LinkStore: Send + Sync {
+    /// Inserts a new link, returning `AppError::Conflict` if the short code is taken.
+    async fn create_link(&self, link: &Link) -> Result<(), AppError>;
+
+    /// Looks up a link regardless of whether it has expired. Redirects serve
+    /// expired links their fallback, so callers decide what expiry means.
+    async fn find_link_by_code(&self, short_code: &str) -> Result<Option<Link>, AppError>;
+
+    /// Newest links first.
+    async fn list_links(&self, limit: i64, offset: i64) -> Result<Vec<Link>, AppError>;
+
+    async fn update_link(
+        &self,
+        short_code: &str,
+        original_url: &str,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<Option<Link>, AppError>;
+
+    /// Finds an earlier generated, non-expiring, unprotected link for the same
+    /// destination and owner that redirects with `redirect_status`, for
+    /// deduplicated creation.
+    async fn find_duplicate_link(
+        &self,
+        original_url: &str,
+        owner: Option<&str>,
+        redirect_status: RedirectStatus,
+    ) -> Result<Option<Link>, AppError>;
+
+    /// Claims an idempotency key for a new request. Returns `None` when the key is
+    /// now ours, otherwise the existing claim. Claims older than a day are reused.
+    async fn claim_idempotency_key(&self, key: &str, fingerprint: &str) -> Result<Option<IdempotencyRecord>, AppError>;
+
+    async fn complete_idempotency_key(&self, key: &str, short_code: &str) -> Result<(), AppError>;
+
+    /// Drops a claim that never completed, so the key can be retried.
+    async fn release_idempotency_key(&self, key: &str) -> Result<(), AppError>;
+
+    async fn delete_link(&self, short_code: &str) -> Result<bool, AppError>;
+
+    /// Applies buffered click counts; `short_codes[i]` receives `counts[i]`
+    /// additional clicks. Returns how many links were updated.
+    async fn add_clicks(&self, short_codes: &[String], counts: &[i64]) -> Result<u64, AppError>;
+
+    /// Counts one click immediately, refusing it once the link has reached its
+    /// `max_clicks`. The check and increment are a single atomic update, so
+    /// concurrent redirects can never overshoot the limit. Returns `false` when
+    /// the click was refused or the link doesn't exist.
+    async fn consume_click(&self, short_code: &str) -> Result<bool, AppError>;
+
+    async fn record_click(&self, event: &ClickEvent) -> Result<(), AppError>;
 
-pub struct DbService {
-    pool: PgPool,
+    /// Aggregates click events since `since`, for one link or, with `None`, the whole instance.
+    async fn get_analytics(&self, short_code: Option<&str>, since: OffsetDateTime) -> Result<AnalyticsResponse, AppError>;
+
+    /// Clicks per A/B variant of one link since `since`, as `(variant, clicks)`
+    /// ordered by variant. Variants without clicks are left out.
+    async fn get_variant_clicks(&self, short_code: &str, since: OffsetDateTime) -> Result<Vec<(i64, i64)>, AppError>;
+
+    async fn count_links(&self) -> Result<i64, AppError>;
+
+    async fn top_links_since(&self, since: OffsetDateTime, limit: i64) -> Result<Vec<TopLink>, AppError>;
+
+    async fn get_links_by_codes(&self, short_codes: &[String]) -> Result<Vec<Link>, AppError>;
+
+    /// Next value of the counter behind sequential short codes.
+    async fn next_code_sequence(&self) -> Result<u64, AppError>;
+
+    async fn short_code_exists(&self, short_code: &str) -> Result<bool, AppError>;
+}
+
+/// Opens the backend named by the scheme of `database_url` and brings its schema
+/// up to date: `postgres://` or `postgresql://`, `sqlite:`, or `memory://` for a
+/// throwaway store that lives as long as the process.
+pub async fn connect(database_url: &str) -> Result<Arc<dyn LinkStore>, AppError> {
+    let scheme = database_url.split(':').next().unwrap_or_default();
+
+    let store: Arc<dyn LinkStore> = match scheme {
+        "postgres" | "postgresql" => Arc::new(PostgresStore::connect(database_url).await?),
+        "sqlite" => Arc::new(SqliteStore::connect(database_url).await?),
+        "memory" => Arc::new(MemoryStore::new()),
+        _ => {
+            return Err(AppError::Database(sqlx::Error::Configuration(
+                format!("unsupported DATABASE_URL scheme: {}", scheme).into(),
+            )))
+        }
+    };
+
+    Ok(store)
+}
+
+/// Reduces a referrer to its host, leaving values that aren't absolute URLs as
+/// they are, so analytics group every page of a site together.
+fn referrer_host(referrer: Option<&str>) -> String {
+    let Some(referrer) = referrer else {
+        return String::new();
+    };
+
+    Url::parse(referrer)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| referrer.to_string())
 }
 
-impl DbService {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+/// Folds per-referrer counts into per-host counts for the backends that can't
+/// do it in SQL.
+fn group_referrers<I>(rows: I) -> Vec<ReferrerClicks>
+where
+    I: IntoIterator<Item = (Option<String>, i64)>,
+{
+    let mut by_host: HashMap<String, i64> = HashMap::new();
+    for (referrer, clicks) in rows {
+        *by_host.entry(referrer_host(referrer.as_deref())).or_default() += clicks;
     }
 
-    pub async fn create_link(&self, link: &Link) -> Result<(), AppError> {
-        sqlx::query!(
-            r#"
-            INSERT INTO links (id, short_code, original_url, clicks, created_at, expires_at)
-            VALUES ($1, $2, $3, $4, $5, $6)
-            "#,
-            link.id,
-            link.short_code,
-            link.original_url,
-            link.clicks,
-            link.created_at,
-            link.expires_at
-        )
-        .execute(&self.pool)
-        .await?;
-
-        Ok(())
+    top_counts(by_host, BREAKDOWN_LIMIT)
+        .into_iter()
+        .map(|(referrer, clicks)| ReferrerClicks { referrer, clicks })
+        .collect()
+}
+
+/// Highest counts first, ties broken by name, as the Postgres queries order them.
+fn top_counts(counts: HashMap<String, i64>, limit: usize) -> Vec<(String, i64)> {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(limit);
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::link::{Destination, DeviceKind, DeviceTarget, GeoTarget, Link, Platform, RedirectStatus, ScheduleTarget};
+    use sqlx::types::Json;
+    use time::Duration;
+
+    fn click(short_code: &str, referrer: Option<&str>, ip_hash: &str) -> ClickEvent {
+        ClickEvent {
+            short_code: short_code.to_string(),
+            clicked_at: OffsetDateTime::now_utc(),
+            referrer: referrer.map(str::to_string),
+            user_agent: None,
+            ip_hash: Some(ip_hash.to_string()),
+            country: None,
+            matched_rule: None,
+            variant: None,
+        }
     }
 
-    pub async fn get_link_by_code(&self, short_code: &str) -> Result<Option<Link>, AppError> {
-        let link = sqlx::query_as!(
-            Link,
-            r#"
-            SELECT id, short_code, original_url, clicks as "clicks!: i64", created_at as "created_at!: _", expires_at
-            FROM links 
-            WHERE short_code = $1 
-            AND (expires_at IS NULL OR expires_at > NOW())
-            "#,
-            short_code
-        )
-        .fetch_optional(&self.pool)
-        .await?;
-
-        Ok(link)
+    /// Behaviour every backend must share; run against each one below.
+    async fn check_store(store: &dyn LinkStore) {
+        store.create_link(&Link::for_url("abc", "https://example.com/a")).await.unwrap();
+        assert!(matches!(
+            store.create_link(&Link::for_url("abc", "https://example.com/b")).await,
+            Err(AppError::Conflict)
+        ));
+        assert!(store.short_code_exists("abc").await.unwrap());
+        assert!(!store.short_code_exists("missing").await.unwrap());
+
+        let expired = Link {
+            expires_at: Some(OffsetDateTime::now_utc() - Duration::hours(1)),
+            ..Link::for_url("old", "https://example.com/old")
+        };
+        store.create_link(&expired).await.unwrap();
+        assert!(store.find_link_by_code("old").await.unwrap().is_some());
+
+        let regional = Link {
+            geo_targets: Json(vec![GeoTarget {
+                countries: vec!["DE".to_string(), "AT".to_string()],
+                url: "https://example.de/shop".to_string(),
+            }]),
+            ..Link::for_url("shop", "https://example.com/shop")
+        };
+        store.create_link(&regional).await.unwrap();
+        let stored = store.find_link_by_code("shop").await.unwrap().unwrap();
+        assert_eq!(stored.geo_targets, regional.geo_targets);
+        assert!(store.find_duplicate_link("https://example.com/shop", None, RedirectStatus::Found).await.unwrap().is_none());
+        assert!(store.delete_link("shop").await.unwrap());
+
+        let app = Link {
+            device_targets: Json(vec![DeviceTarget {
+                device: Some(DeviceKind::Mobile),
+                os: Some(Platform::Ios),
+                url: "https://apps.apple.com/app/id1".to_string(),
+            }]),
+            ..Link::for_url("app", "https://example.com/app")
+        };
+        store.create_link(&app).await.unwrap();
+        let stored = store.find_link_by_code("app").await.unwrap().unwrap();
+        assert_eq!(stored.device_targets, app.device_targets);
+        assert!(store.find_duplicate_link("https://example.com/app", None, RedirectStatus::Found).await.unwrap().is_none());
+        assert!(store.delete_link("app").await.unwrap());
+
+        let support = Link {
+            schedule_targets: Json(vec![ScheduleTarget {
+                cron: "* 9-17 * * MON-FRI".to_string(),
+                timezone: "Europe/Berlin".to_string(),
+                url: "https://example.com/chat".to_string(),
+            }]),
+            ..Link::for_url("support", "https://example.com/help")
+        };
+        store.create_link(&support).await.unwrap();
+        let stored = store.find_link_by_code("support").await.unwrap().unwrap();
+        assert_eq!(stored.schedule_targets, support.schedule_targets);
+        assert!(store.find_duplicate_link("https://example.com/help", None, RedirectStatus::Found).await.unwrap().is_none());
+        assert!(store.delete_link("support").await.unwrap());
+
+        let split = Link {
+            destinations: Json(vec![
+                Destination { url: "https://example.com/a".to_string(), weight: 70 },
+                Destination { url: "https://example.com/b".to_string(), weight: 30 },
+            ]),
+            sticky_variants: true,
+            ..Link::for_url("ab", "https://example.com/landing")
+        };
+        store.create_link(&split).await.unwrap();
+        let stored = store.find_link_by_code("ab").await.unwrap().unwrap();
+        assert_eq!(stored.destinations, split.destinations);
+        assert!(stored.sticky_variants);
+        assert!(store.find_duplicate_link("https://example.com/landing", None, RedirectStatus::Found).await.unwrap().is_none());
+        for variant in [1, 0, 1] {
+            store.record_click(&ClickEvent { variant: Some(variant), ..click("ab", None, "v") }).await.unwrap();
+        }
+        let since = OffsetDateTime::now_utc() - Duration::days(1);
+        assert_eq!(store.get_variant_clicks("ab", since).await.unwrap(), vec![(0, 1), (1, 2)]);
+        assert!(store.delete_link("ab").await.unwrap());
+        assert!(store.get_variant_clicks("ab", since).await.unwrap().is_empty());
+
+        let fallback = Link {
+            expired_redirect_url: Some("https://example.com/landing".to_string()),
+            ..Link::for_url("promo", "https://example.com/promo")
+        };
+        store.create_link(&fallback).await.unwrap();
+        let stored = store.find_link_by_code("promo").await.unwrap().unwrap();
+        assert_eq!(stored.expired_redirect_url.as_deref(), Some("https://example.com/landing"));
+        assert!(store.find_duplicate_link("https://example.com/promo", None, RedirectStatus::Found).await.unwrap().is_none());
+        assert_eq!(stored.redirect_status, RedirectStatus::Found);
+        assert!(store.delete_link("promo").await.unwrap());
+        assert_eq!(store.count_links().await.unwrap(), 2);
+
+        let duplicate = store.find_duplicate_link("https://EXAMPLE.com/a", None, RedirectStatus::Found).await.unwrap();
+        assert_eq!(duplicate.map(|link| link.short_code).as_deref(), Some("abc"));
+        assert!(store.find_duplicate_link("https://example.com/a", Some("bob"), RedirectStatus::Found).await.unwrap().is_none());
+
+        let protected = Link {
+            password_hash: Some("$argon2id$hash".to_string()),
+            ..Link::for_url("pw", "https://example.com/secret")
+        };
+        store.create_link(&protected).await.unwrap();
+        let stored = store.find_link_by_code("pw").await.unwrap().unwrap();
+        assert_eq!(stored.password_hash.as_deref(), Some("$argon2id$hash"));
+        assert!(store.find_duplicate_link("https://example.com/secret", None, RedirectStatus::Found).await.unwrap().is_none());
+        assert!(store.delete_link("pw").await.unwrap());
+
+        let limited = Link {
+            max_clicks: Some(1),
+            ..Link::for_url("once", "https://example.com/download")
+        };
+        store.create_link(&limited).await.unwrap();
+        assert!(store.find_duplicate_link("https://example.com/download", None, RedirectStatus::Found).await.unwrap().is_none());
+        assert!(store.consume_click("once").await.unwrap());
+        assert!(!store.consume_click("once").await.unwrap());
+        assert_eq!(store.find_link_by_code("once").await.unwrap().unwrap().clicks, 1);
+        assert!(!store.consume_click("missing").await.unwrap());
+        assert!(store.delete_link("once").await.unwrap());
+
+        let scheduled = Link {
+            activates_at: Some(OffsetDateTime::now_utc() + Duration::hours(1)),
+            ..Link::for_url("soon", "https://example.com/launch")
+        };
+        store.create_link(&scheduled).await.unwrap();
+        let stored = store.find_link_by_code("soon").await.unwrap().unwrap();
+        assert_eq!(stored.activates_at.map(|at| at.unix_timestamp()), scheduled.activates_at.map(|at| at.unix_timestamp()));
+        assert!(store.find_duplicate_link("https://example.com/launch", None, RedirectStatus::Found).await.unwrap().is_none());
+        assert!(store.delete_link("soon").await.unwrap());
+
+        // A plain create expects the default status, not a link someone made permanent.
+        let moved = Link { redirect_status: RedirectStatus::MovedPermanently, ..Link::for_url("moved", "https://example.com/moved") };
+        store.create_link(&moved).await.unwrap();
+        assert!(store.find_duplicate_link("https://example.com/moved", None, RedirectStatus::Found).await.unwrap().is_none());
+        let duplicate = store.find_duplicate_link("https://example.com/moved", None, RedirectStatus::MovedPermanently).await.unwrap();
+        assert_eq!(duplicate.map(|link| link.short_code).as_deref(), Some("moved"));
+        assert!(store.delete_link("moved").await.unwrap());
+
+        let permanent = Link {
+            redirect_status: RedirectStatus::PermanentRedirect,
+            forward_path: true,
+            ..Link::for_url("perm", "https://example.com/perm")
+        };
+        store.create_link(&permanent).await.unwrap();
+        let stored = store.find_link_by_code("perm").await.unwrap().unwrap();
+        assert_eq!(stored.redirect_status, RedirectStatus::PermanentRedirect);
+        assert!(stored.forward_path && !stored.forward_query);
+        assert!(stored.geo_targets.is_empty() && stored.device_targets.is_empty());
+        assert!(stored.destinations.is_empty() && !stored.sticky_variants);
+        assert!(store.find_duplicate_link("https://example.com/perm", None, RedirectStatus::Found).await.unwrap().is_none());
+        assert!(store.delete_link("perm").await.unwrap());
+
+        let updated = store.update_link("abc", "https://example.org", None).await.unwrap().unwrap();
+        assert_eq!(updated.original_url, "https://example.org");
+        assert!(store.update_link("missing", "https://example.org", None).await.unwrap().is_none());
+
+        assert_eq!(store.add_clicks(&["abc".to_string(), "missing".to_string()], &[3, 1]).await.unwrap(), 1);
+        assert_eq!(store.find_link_by_code("abc").await.unwrap().unwrap().clicks, 3);
+
+        store.record_click(&click("abc", Some("https://news.example/item?id=1"), "a")).await.unwrap();
+        store.record_click(&click("abc", Some("https://news.example/other"), "a")).await.unwrap();
+        store.record_click(&ClickEvent { matched_rule: Some("device:0".to_string()), ..click("abc", None, "b") }).await.unwrap();
+
+        let since = OffsetDateTime::now_utc() - Duration::days(1);
+        let analytics = store.get_analytics(Some("abc"), since).await.unwrap();
+        assert_eq!(analytics.total_clicks, 3);
+        assert_eq!(analytics.unique_clicks, 2);
+        assert_eq!(analytics.clicks_by_date.len(), 1);
+        assert_eq!(analytics.clicks_by_country[0].country, "Unknown");
+        assert_eq!(analytics.clicks_by_referrer[0].referrer, "news.example");
+        assert_eq!(analytics.clicks_by_referrer[0].clicks, 2);
+        assert_eq!((analytics.clicks_by_rule[0].rule.as_str(), analytics.clicks_by_rule[0].clicks), ("default", 2));
+        assert_eq!(analytics.clicks_by_rule[1].rule, "device:0");
+        assert_eq!(store.get_analytics(Some("old"), since).await.unwrap().total_clicks, 0);
+
+        let top = store.top_links_since(since, 10).await.unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!((top[0].short_code.as_str(), top[0].clicks), ("abc", 3));
+
+        let listed = store.list_links(1, 0).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        let found = store.get_links_by_codes(&["abc".to_string(), "old".to_string()]).await.unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(store.get_links_by_codes(&[]).await.unwrap().is_empty());
+
+        assert!(store.claim_idempotency_key("key", "fp").await.unwrap().is_none());
+        let pending = store.claim_idempotency_key("key", "fp").await.unwrap().unwrap();
+        assert_eq!(pending.short_code, None);
+        store.complete_idempotency_key("key", "abc").await.unwrap();
+        store.release_idempotency_key("key").await.unwrap();
+        let completed = store.claim_idempotency_key("key", "other").await.unwrap().unwrap();
+        assert_eq!((completed.fingerprint.as_str(), completed.short_code.as_deref()), ("fp", Some("abc")));
+
+        let first = store.next_code_sequence().await.unwrap();
+        assert_eq!(store.next_code_sequence().await.unwrap(), first + 1);
+
+        assert!(store.delete_link("abc").await.unwrap());
+        assert!(!store.delete_link("abc").await.unwrap());
+        assert_eq!(store.get_analytics(Some("abc"), since).await.unwrap().total_clicks, 0);
     }
 
-    pub async fn increment_clicks(&self, short_code: &str) -> Result<(), AppError> {
-        sqlx::query!(
-            "UPDATE links SET clicks = clicks + 1 WHERE short_code = $1",
-            short_code
-        )
-        .execute(&self.pool)
-        .await?;
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = connect("memory://").await.unwrap();
+        check_store(store.as_ref()).await;
+    }
 
-        Ok(())
+    #[tokio::test]
+    async fn test_sqlite_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("sqlite://{}", dir.path().join("links.db").display());
+
+        let store = connect(&url).await.unwrap();
+        check_store(store.as_ref()).await;
+    }
+
+    #[tokio::test]
+    async fn test_unknown_scheme_is_rejected() {
+        assert!(connect("mysql://localhost/links").await.is_err());
     }
 
-    pub async fn short_code_exists(&self, short_code: &str) -> Result<bool, AppError> {
-        let exists = sqlx::query!(
-            "SELECT EXISTS(SELECT 1 FROM links WHERE short_code = $1)",
-            short_code
-        )
-        .fetch_one(&self.pool)
-        .await?
-        .exists
-        .unwrap_or(false);
-
-        Ok(exists)
+    #[test]
+    fn test_referrer_host() {
+        assert_eq!(referrer_host(Some("https://news.example/a?b=c")), "news.example");
+        assert_eq!(referrer_host(Some("android-app")), "android-app");
+        assert_eq!(referrer_host(None), "");
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_group_referrers_merges_hosts() {
+        let grouped = group_referrers(vec![
+            (Some("https://a.example/1".to_string()), 2),
+            (Some("https://a.example/2".to_string()), 1),
+            (Some("https://b.example/".to_string()), 3),
+        ]);
+
+        let grouped: Vec<_> = grouped.iter().map(|r| (r.referrer.as_str(), r.clicks)).collect();
+        assert_eq!(grouped, vec![("a.example", 3), ("b.example", 3)]);
+    }
+}
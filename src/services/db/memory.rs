@@ -0,0 +1,299 @@
+use async_trait::async_trait;
+use sqlx::types::time::OffsetDateTime;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use crate::models::analytics::{AnalyticsResponse, ClickEvent, CountryClicks, DateClicks, RuleClicks, TopLink};
+use crate::models::link::{IdempotencyRecord, Link, RedirectStatus};
+use crate::error::AppError;
+use crate::services::db::{group_referrers, top_counts, LinkStore, BREAKDOWN_LIMIT};
+use crate::services::shortener::normalize_url;
+
+struct IdempotencyClaim {
+    record: IdempotencyRecord,
+    created_at: OffsetDateTime,
+}
+
+#[derive(Default)]
+struct State {
+    links: HashMap<String, Link>,
+    clicks: Vec<ClickEvent>,
+    idempotency_keys: HashMap<String, IdempotencyClaim>,
+    next_sequence: u64,
+}
+
+/// Process-local backend for tests and throwaway instances; nothing survives a
+/// restart.
+#[derive(Default)]
+pub struct MemoryStore {
+    state: Mutex<State>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LinkStore for MemoryStore {
+    async fn create_link(&self, link: &Link) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        if state.links.contains_key(&link.short_code) {
+            return Err(AppError::Conflict);
+        }
+
+        state.links.insert(link.short_code.clone(), link.clone());
+        Ok(())
+    }
+
+    async fn find_link_by_code(&self, short_code: &str) -> Result<Option<Link>, AppError> {
+        Ok(self.state.lock().unwrap().links.get(short_code).cloned())
+    }
+
+    async fn list_links(&self, limit: i64, offset: i64) -> Result<Vec<Link>, AppError> {
+        let state = self.state.lock().unwrap();
+        let mut links: Vec<Link> = state.links.values().cloned().collect();
+        links.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.short_code.cmp(&b.short_code)));
+
+        Ok(links
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .collect())
+    }
+
+    async fn update_link(
+        &self,
+        short_code: &str,
+        original_url: &str,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<Option<Link>, AppError> {
+        let mut state = self.state.lock().unwrap();
+        let Some(link) = state.links.get_mut(short_code) else {
+            return Ok(None);
+        };
+
+        link.original_url = original_url.to_string();
+        link.expires_at = expires_at;
+        Ok(Some(link.clone()))
+    }
+
+    async fn find_duplicate_link(
+        &self,
+        original_url: &str,
+        owner: Option<&str>,
+        redirect_status: RedirectStatus,
+    ) -> Result<Option<Link>, AppError> {
+        let normalized = normalize_url(original_url);
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .links
+            .values()
+            .filter(|link| {
+                !link.is_custom
+                    && link.expires_at.is_none()
+                    && link.password_hash.is_none()
+                    && link.max_clicks.is_none()
+                    && link.activates_at.is_none()
+                    && link.expired_redirect_url.is_none()
+                    && !link.forward_query
+                    && !link.forward_path
+                    && link.redirect_status == redirect_status
+                    && link.geo_targets.is_empty()
+                    && link.device_targets.is_empty()
+                    && link.schedule_targets.is_empty()
+                    && link.destinations.is_empty()
+                    && link.owner.as_deref() == owner
+                    && normalize_url(&link.original_url) == normalized
+            })
+            .min_by_key(|link| link.created_at)
+            .cloned())
+    }
+
+    async fn claim_idempotency_key(&self, key: &str, fingerprint: &str) -> Result<Option<IdempotencyRecord>, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let mut state = self.state.lock().unwrap();
+
+        match state.idempotency_keys.get(key) {
+            Some(claim) if claim.created_at >= now - time::Duration::hours(24) => Ok(Some(IdempotencyRecord {
+                fingerprint: claim.record.fingerprint.clone(),
+                short_code: claim.record.short_code.clone(),
+            })),
+            _ => {
+                state.idempotency_keys.insert(
+                    key.to_string(),
+                    IdempotencyClaim {
+                        record: IdempotencyRecord {
+                            fingerprint: fingerprint.to_string(),
+                            short_code: None,
+                        },
+                        created_at: now,
+                    },
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    async fn complete_idempotency_key(&self, key: &str, short_code: &str) -> Result<(), AppError> {
+        if let Some(claim) = self.state.lock().unwrap().idempotency_keys.get_mut(key) {
+            claim.record.short_code = Some(short_code.to_string());
+        }
+
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, key: &str) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        if state.idempotency_keys.get(key).is_some_and(|claim| claim.record.short_code.is_none()) {
+            state.idempotency_keys.remove(key);
+        }
+
+        Ok(())
+    }
+
+    async fn delete_link(&self, short_code: &str) -> Result<bool, AppError> {
+        let mut state = self.state.lock().unwrap();
+        if state.links.remove(short_code).is_none() {
+            return Ok(false);
+        }
+
+        state.clicks.retain(|event| event.short_code != short_code);
+        Ok(true)
+    }
+
+    async fn add_clicks(&self, short_codes: &[String], counts: &[i64]) -> Result<u64, AppError> {
+        let mut state = self.state.lock().unwrap();
+        let mut updated = 0;
+
+        for (short_code, count) in short_codes.iter().zip(counts) {
+            if let Some(link) = state.links.get_mut(short_code) {
+                link.clicks += count;
+                updated += 1;
+            }
+        }
+
+        Ok(updated)
+    }
+
+    async fn consume_click(&self, short_code: &str) -> Result<bool, AppError> {
+        let mut state = self.state.lock().unwrap();
+        match state.links.get_mut(short_code) {
+            Some(link) if link.max_clicks.is_none_or(|max_clicks| link.clicks < max_clicks) => {
+                link.clicks += 1;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn record_click(&self, event: &ClickEvent) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.links.contains_key(&event.short_code) {
+            return Err(AppError::NotFound);
+        }
+
+        state.clicks.push(event.clone());
+        Ok(())
+    }
+
+    async fn get_analytics(&self, short_code: Option<&str>, since: OffsetDateTime) -> Result<AnalyticsResponse, AppError> {
+        let state = self.state.lock().unwrap();
+        let events: Vec<&ClickEvent> = state
+            .clicks
+            .iter()
+            .filter(|event| short_code.is_none_or(|code| event.short_code == code) && event.clicked_at >= since)
+            .collect();
+
+        let unique_clicks = events.iter().filter_map(|event| event.ip_hash.as_deref()).collect::<HashSet<_>>().len();
+
+        let mut by_date: HashMap<String, i64> = HashMap::new();
+        let mut by_country: HashMap<String, i64> = HashMap::new();
+        let mut by_referrer: HashMap<Option<String>, i64> = HashMap::new();
+        let mut by_rule: HashMap<String, i64> = HashMap::new();
+        for event in &events {
+            let date = event.clicked_at.date();
+            *by_date.entry(format!("{:04}-{:02}-{:02}", date.year(), date.month() as u8, date.day())).or_default() += 1;
+            *by_country.entry(event.country.clone().unwrap_or_else(|| "Unknown".to_string())).or_default() += 1;
+            *by_referrer.entry(event.referrer.clone()).or_default() += 1;
+            *by_rule.entry(event.matched_rule.clone().unwrap_or_else(|| "default".to_string())).or_default() += 1;
+        }
+
+        let mut clicks_by_date: Vec<DateClicks> = by_date
+            .into_iter()
+            .map(|(date, clicks)| DateClicks { date, clicks })
+            .collect();
+        clicks_by_date.sort_by(|a, b| a.date.cmp(&b.date));
+
+        Ok(AnalyticsResponse {
+            total_clicks: events.len() as i64,
+            unique_clicks: unique_clicks as i64,
+            clicks_by_date,
+            clicks_by_country: top_counts(by_country, BREAKDOWN_LIMIT)
+                .into_iter()
+                .map(|(country, clicks)| CountryClicks { country, clicks })
+                .collect(),
+            clicks_by_referrer: group_referrers(by_referrer),
+            clicks_by_rule: top_counts(by_rule, BREAKDOWN_LIMIT)
+                .into_iter()
+                .map(|(rule, clicks)| RuleClicks { rule, clicks })
+                .collect(),
+            clicks_by_variant: vec![],
+            top_links: vec![],
+        })
+    }
+
+    async fn get_variant_clicks(&self, short_code: &str, since: OffsetDateTime) -> Result<Vec<(i64, i64)>, AppError> {
+        let state = self.state.lock().unwrap();
+        let mut counts: BTreeMap<i64, i64> = BTreeMap::new();
+        for event in state.clicks.iter().filter(|event| event.short_code == short_code && event.clicked_at >= since) {
+            if let Some(variant) = event.variant {
+                *counts.entry(variant.into()).or_default() += 1;
+            }
+        }
+
+        Ok(counts.into_iter().collect())
+    }
+
+    async fn count_links(&self) -> Result<i64, AppError> {
+        Ok(self.state.lock().unwrap().links.len() as i64)
+    }
+
+    async fn top_links_since(&self, since: OffsetDateTime, limit: i64) -> Result<Vec<TopLink>, AppError> {
+        let state = self.state.lock().unwrap();
+        let mut counts: HashMap<String, i64> = HashMap::new();
+        for event in state.clicks.iter().filter(|event| event.clicked_at >= since) {
+            *counts.entry(event.short_code.clone()).or_default() += 1;
+        }
+
+        Ok(top_counts(counts, limit.max(0) as usize)
+            .into_iter()
+            .filter_map(|(short_code, clicks)| {
+                let original_url = state.links.get(&short_code)?.original_url.clone();
+                Some(TopLink { short_code, original_url, clicks })
+            })
+            .collect())
+    }
+
+    async fn get_links_by_codes(&self, short_codes: &[String]) -> Result<Vec<Link>, AppError> {
+        let state = self.state.lock().unwrap();
+
+        Ok(short_codes
+            .iter()
+            .filter_map(|short_code| state.links.get(short_code).cloned())
+            .collect())
+    }
+
+    async fn next_code_sequence(&self) -> Result<u64, AppError> {
+        let mut state = self.state.lock().unwrap();
+        let value = state.next_sequence;
+        state.next_sequence += 1;
+
+        Ok(value)
+    }
+
+    async fn short_code_exists(&self, short_code: &str) -> Result<bool, AppError> {
+        Ok(self.state.lock().unwrap().links.contains_key(short_code))
+    }
+}
@@ -0,0 +1,523 @@
+use async_trait::async_trait;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::types::{time::OffsetDateTime, Json};
+use std::time::Duration;
+use crate::models::analytics::{
+    AnalyticsResponse, ClickEvent, CountryClicks, DateClicks, ReferrerClicks, RuleClicks, TopLink,
+};
+use crate::models::link::{Destination, DeviceTarget, GeoTarget, IdempotencyRecord, Link, RedirectStatus, ScheduleTarget};
+use crate::error::AppError;
+use crate::services::db::LinkStore;
+use crate::services::shortener::normalize_url;
+
+pub struct PostgresStore {
+    pool: PgPool,
+}
+
+impl PostgresStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn connect(database_url: &str) -> Result<Self, AppError> {
+        let pool = PgPoolOptions::new()
+            .max_connections(100)
+            .min_connections(10)
+            .max_lifetime(Duration::from_secs(1800))
+            .idle_timeout(Duration::from_secs(600))
+            .connect(database_url)
+            .await?;
+
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .map_err(sqlx::Error::from)?;
+
+        Ok(Self::new(pool))
+    }
+}
+
+#[async_trait]
+impl LinkStore for PostgresStore {
+    async fn create_link(&self, link: &Link) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO links (id, short_code, original_url, clicks, created_at, expires_at, owner, normalized_url, is_custom, password_hash, max_clicks, activates_at, expired_redirect_url, redirect_status,
+                forward_query, forward_path, geo_targets, device_targets, schedule_targets, sticky_variants)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)
+            "#,
+            link.id,
+            link.short_code,
+            link.original_url,
+            link.clicks,
+            link.created_at,
+            link.expires_at,
+            link.owner,
+            normalize_url(&link.original_url),
+            link.is_custom,
+            link.password_hash,
+            link.max_clicks,
+            link.activates_at,
+            link.expired_redirect_url,
+            link.redirect_status as i16,
+            link.forward_query,
+            link.forward_path,
+            &link.geo_targets as _,
+            &link.device_targets as _,
+            &link.schedule_targets as _,
+            link.sticky_variants
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => AppError::Conflict,
+            _ => AppError::Database(e),
+        })?;
+
+        if !link.destinations.is_empty() {
+            let urls: Vec<String> = link.destinations.iter().map(|destination| destination.url.clone()).collect();
+            let weights: Vec<i32> = link.destinations.iter().map(|destination| destination.weight as i32).collect();
+            sqlx::query!(
+                r#"
+                INSERT INTO link_destinations (link_id, position, url, weight)
+                SELECT $1, (ordinality - 1)::smallint, url, weight
+                FROM UNNEST($2::text[], $3::int[]) WITH ORDINALITY AS d(url, weight, ordinality)
+                "#,
+                link.id,
+                &urls,
+                &weights
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn find_link_by_code(&self, short_code: &str) -> Result<Option<Link>, AppError> {
+        let link = sqlx::query_as!(
+            Link,
+            r#"
+            SELECT id, short_code, original_url, clicks as "clicks!: i64", created_at as "created_at!: _", expires_at,
+                owner, is_custom as "is_custom!", password_hash, max_clicks, activates_at, expired_redirect_url,
+                redirect_status as "redirect_status: RedirectStatus", forward_query, forward_path,
+                geo_targets as "geo_targets: Json<Vec<GeoTarget>>", device_targets as "device_targets: Json<Vec<DeviceTarget>>",
+                schedule_targets as "schedule_targets: Json<Vec<ScheduleTarget>>", sticky_variants,
+                (SELECT COALESCE(jsonb_agg(jsonb_build_object('url', d.url, 'weight', d.weight) ORDER BY d.position), '[]')
+                    FROM link_destinations d WHERE d.link_id = links.id) as "destinations!: Json<Vec<Destination>>"
+            FROM links 
+            WHERE short_code = $1
+            "#,
+            short_code
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    async fn list_links(&self, limit: i64, offset: i64) -> Result<Vec<Link>, AppError> {
+        let links = sqlx::query_as!(
+            Link,
+            r#"
+            SELECT id, short_code, original_url, clicks as "clicks!: i64", created_at as "created_at!: _", expires_at,
+                owner, is_custom as "is_custom!", password_hash, max_clicks, activates_at, expired_redirect_url,
+                redirect_status as "redirect_status: RedirectStatus", forward_query, forward_path,
+                geo_targets as "geo_targets: Json<Vec<GeoTarget>>", device_targets as "device_targets: Json<Vec<DeviceTarget>>",
+                schedule_targets as "schedule_targets: Json<Vec<ScheduleTarget>>", sticky_variants,
+                (SELECT COALESCE(jsonb_agg(jsonb_build_object('url', d.url, 'weight', d.weight) ORDER BY d.position), '[]')
+                    FROM link_destinations d WHERE d.link_id = links.id) as "destinations!: Json<Vec<Destination>>"
+            FROM links 
+            ORDER BY created_at DESC, short_code
+            LIMIT $1 OFFSET $2
+            "#,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(links)
+    }
+
+    async fn update_link(
+        &self,
+        short_code: &str,
+        original_url: &str,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<Option<Link>, AppError> {
+        let link = sqlx::query_as!(
+            Link,
+            r#"
+            UPDATE links 
+            SET original_url = $2, expires_at = $3, normalized_url = $4
+            WHERE short_code = $1
+            RETURNING id, short_code, original_url, clicks as "clicks!: i64", created_at as "created_at!: _", expires_at,
+                owner, is_custom as "is_custom!", password_hash, max_clicks, activates_at, expired_redirect_url,
+                redirect_status as "redirect_status: RedirectStatus", forward_query, forward_path,
+                geo_targets as "geo_targets: Json<Vec<GeoTarget>>", device_targets as "device_targets: Json<Vec<DeviceTarget>>",
+                schedule_targets as "schedule_targets: Json<Vec<ScheduleTarget>>", sticky_variants,
+                (SELECT COALESCE(jsonb_agg(jsonb_build_object('url', d.url, 'weight', d.weight) ORDER BY d.position), '[]')
+                    FROM link_destinations d WHERE d.link_id = links.id) as "destinations!: Json<Vec<Destination>>"
+            "#,
+            short_code,
+            original_url,
+            expires_at,
+            normalize_url(original_url)
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    async fn find_duplicate_link(
+        &self,
+        original_url: &str,
+        owner: Option<&str>,
+        redirect_status: RedirectStatus,
+    ) -> Result<Option<Link>, AppError> {
+        let link = sqlx::query_as!(
+            Link,
+            r#"
+            SELECT id, short_code, original_url, clicks as "clicks!: i64", created_at as "created_at!: _", expires_at,
+                owner, is_custom as "is_custom!", password_hash, max_clicks, activates_at, expired_redirect_url,
+                redirect_status as "redirect_status: RedirectStatus", forward_query, forward_path,
+                geo_targets as "geo_targets: Json<Vec<GeoTarget>>", device_targets as "device_targets: Json<Vec<DeviceTarget>>",
+                schedule_targets as "schedule_targets: Json<Vec<ScheduleTarget>>", sticky_variants,
+                (SELECT COALESCE(jsonb_agg(jsonb_build_object('url', d.url, 'weight', d.weight) ORDER BY d.position), '[]')
+                    FROM link_destinations d WHERE d.link_id = links.id) as "destinations!: Json<Vec<Destination>>"
+            FROM links 
+            WHERE normalized_url = $1
+            AND owner IS NOT DISTINCT FROM $2
+            AND NOT is_custom
+            AND expires_at IS NULL
+            AND password_hash IS NULL
+            AND max_clicks IS NULL
+            AND activates_at IS NULL
+            AND expired_redirect_url IS NULL
+            AND NOT forward_query
+            AND NOT forward_path
+            AND redirect_status = $3
+            AND geo_targets = '[]'::jsonb
+            AND device_targets = '[]'::jsonb
+            AND schedule_targets = '[]'::jsonb
+            AND NOT EXISTS (SELECT 1 FROM link_destinations d WHERE d.link_id = links.id)
+            ORDER BY created_at
+            LIMIT 1
+            "#,
+            normalize_url(original_url),
+            owner,
+            redirect_status as i16
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    async fn claim_idempotency_key(&self, key: &str, fingerprint: &str) -> Result<Option<IdempotencyRecord>, AppError> {
+        let claimed = sqlx::query!(
+            r#"
+            INSERT INTO idempotency_keys (key, fingerprint, short_code, created_at)
+            VALUES ($1, $2, NULL, NOW())
+            ON CONFLICT (key) DO UPDATE
+            SET fingerprint = EXCLUDED.fingerprint, short_code = NULL, created_at = NOW()
+            WHERE idempotency_keys.created_at < NOW() - INTERVAL '24 hours'
+            RETURNING key
+            "#,
+            key,
+            fingerprint
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if claimed.is_some() {
+            return Ok(None);
+        }
+
+        let existing = sqlx::query_as!(
+            IdempotencyRecord,
+            "SELECT fingerprint, short_code FROM idempotency_keys WHERE key = $1",
+            key
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(existing)
+    }
+
+    async fn complete_idempotency_key(&self, key: &str, short_code: &str) -> Result<(), AppError> {
+        sqlx::query!(
+            "UPDATE idempotency_keys SET short_code = $2 WHERE key = $1",
+            key,
+            short_code
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, key: &str) -> Result<(), AppError> {
+        sqlx::query!(
+            "DELETE FROM idempotency_keys WHERE key = $1 AND short_code IS NULL",
+            key
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_link(&self, short_code: &str) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            "DELETE FROM links WHERE short_code = $1",
+            short_code
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn add_clicks(&self, short_codes: &[String], counts: &[i64]) -> Result<u64, AppError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE links
+            SET clicks = links.clicks + pending.count
+            FROM UNNEST($1::text[], $2::bigint[]) AS pending(short_code, count)
+            WHERE links.short_code = pending.short_code
+            "#,
+            short_codes,
+            counts
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn consume_click(&self, short_code: &str) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE links
+            SET clicks = clicks + 1
+            WHERE short_code = $1
+            AND (max_clicks IS NULL OR clicks < max_clicks)
+            "#,
+            short_code
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn record_click(&self, event: &ClickEvent) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO click_events (short_code, clicked_at, referrer, user_agent, ip_hash, country, matched_rule, variant)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            event.short_code,
+            event.clicked_at,
+            event.referrer,
+            event.user_agent,
+            event.ip_hash,
+            event.country,
+            event.matched_rule,
+            event.variant
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_analytics(
+        &self,
+        short_code: Option<&str>,
+        since: OffsetDateTime,
+    ) -> Result<AnalyticsResponse, AppError> {
+        let totals = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "total_clicks!", COUNT(DISTINCT ip_hash) as "unique_clicks!"
+            FROM click_events
+            WHERE ($1::text IS NULL OR short_code = $1) AND clicked_at >= $2
+            "#,
+            short_code,
+            since
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let clicks_by_date = sqlx::query_as!(
+            DateClicks,
+            r#"
+            SELECT to_char(clicked_at AT TIME ZONE 'UTC', 'YYYY-MM-DD') as "date!", COUNT(*) as "clicks!"
+            FROM click_events
+            WHERE ($1::text IS NULL OR short_code = $1) AND clicked_at >= $2
+            GROUP BY 1
+            ORDER BY 1
+            "#,
+            short_code,
+            since
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let clicks_by_country = sqlx::query_as!(
+            CountryClicks,
+            r#"
+            SELECT COALESCE(country, 'Unknown') as "country!", COUNT(*) as "clicks!"
+            FROM click_events
+            WHERE ($1::text IS NULL OR short_code = $1) AND clicked_at >= $2
+            GROUP BY 1
+            ORDER BY 2 DESC, 1
+            LIMIT 20
+            "#,
+            short_code,
+            since
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let clicks_by_referrer = sqlx::query_as!(
+            ReferrerClicks,
+            r#"
+            SELECT COALESCE(substring(referrer from '^[A-Za-z]+://([^/?#]+)'), referrer, '') as "referrer!", COUNT(*) as "clicks!"
+            FROM click_events
+            WHERE ($1::text IS NULL OR short_code = $1) AND clicked_at >= $2
+            GROUP BY 1
+            ORDER BY 2 DESC, 1
+            LIMIT 20
+            "#,
+            short_code,
+            since
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let clicks_by_rule = sqlx::query_as!(
+            RuleClicks,
+            r#"
+            SELECT COALESCE(matched_rule, 'default') as "rule!", COUNT(*) as "clicks!"
+            FROM click_events
+            WHERE ($1::text IS NULL OR short_code = $1) AND clicked_at >= $2
+            GROUP BY 1
+            ORDER BY 2 DESC, 1
+            LIMIT 20
+            "#,
+            short_code,
+            since
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(AnalyticsResponse {
+            total_clicks: totals.total_clicks,
+            unique_clicks: totals.unique_clicks,
+            clicks_by_date,
+            clicks_by_country,
+            clicks_by_referrer,
+            clicks_by_rule,
+            clicks_by_variant: vec![],
+            top_links: vec![],
+        })
+    }
+
+    async fn get_variant_clicks(&self, short_code: &str, since: OffsetDateTime) -> Result<Vec<(i64, i64)>, AppError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT variant::bigint as "variant!", COUNT(*) as "clicks!"
+            FROM click_events
+            WHERE short_code = $1 AND clicked_at >= $2 AND variant IS NOT NULL
+            GROUP BY 1
+            ORDER BY 1
+            "#,
+            short_code,
+            since
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| (row.variant, row.clicks)).collect())
+    }
+
+    async fn count_links(&self) -> Result<i64, AppError> {
+        let count = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM links"#)
+            .fetch_one(&self.pool)
+            .await?
+            .count;
+
+        Ok(count)
+    }
+
+    async fn top_links_since(&self, since: OffsetDateTime, limit: i64) -> Result<Vec<TopLink>, AppError> {
+        let links = sqlx::query_as!(
+            TopLink,
+            r#"
+            SELECT l.short_code, l.original_url, COUNT(*) as "clicks!"
+            FROM click_events c
+            JOIN links l ON l.short_code = c.short_code
+            WHERE c.clicked_at >= $1
+            GROUP BY l.short_code, l.original_url
+            ORDER BY 3 DESC, 1
+            LIMIT $2
+            "#,
+            since,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(links)
+    }
+
+    async fn get_links_by_codes(&self, short_codes: &[String]) -> Result<Vec<Link>, AppError> {
+        let links = sqlx::query_as!(
+            Link,
+            r#"
+            SELECT id, short_code, original_url, clicks as "clicks!: i64", created_at as "created_at!: _", expires_at,
+                owner, is_custom as "is_custom!", password_hash, max_clicks, activates_at, expired_redirect_url,
+                redirect_status as "redirect_status: RedirectStatus", forward_query, forward_path,
+                geo_targets as "geo_targets: Json<Vec<GeoTarget>>", device_targets as "device_targets: Json<Vec<DeviceTarget>>",
+                schedule_targets as "schedule_targets: Json<Vec<ScheduleTarget>>", sticky_variants,
+                (SELECT COALESCE(jsonb_agg(jsonb_build_object('url', d.url, 'weight', d.weight) ORDER BY d.position), '[]')
+                    FROM link_destinations d WHERE d.link_id = links.id) as "destinations!: Json<Vec<Destination>>"
+            FROM links 
+            WHERE short_code = ANY($1)
+            "#,
+            short_codes
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(links)
+    }
+
+    async fn next_code_sequence(&self) -> Result<u64, AppError> {
+        let value = sqlx::query_scalar!(r#"SELECT nextval('link_code_seq') as "value!""#)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(value as u64)
+    }
+
+    async fn short_code_exists(&self, short_code: &str) -> Result<bool, AppError> {
+        let exists = sqlx::query!(
+            "SELECT EXISTS(SELECT 1 FROM links WHERE short_code = $1)",
+            short_code
+        )
+        .fetch_one(&self.pool)
+        .await?
+        .exists
+        .unwrap_or(false);
+
+        Ok(exists)
+    }
+} 
\ No newline at end of file
@@ -0,0 +1,457 @@
+use async_trait::async_trait;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use sqlx::types::time::OffsetDateTime;
+use sqlx::{QueryBuilder, Sqlite};
+use std::str::FromStr;
+use crate::models::analytics::{AnalyticsResponse, ClickEvent, CountryClicks, DateClicks, RuleClicks, TopLink};
+use crate::models::link::{IdempotencyRecord, Link, RedirectStatus};
+use crate::error::AppError;
+use crate::services::db::{group_referrers, LinkStore, BREAKDOWN_LIMIT};
+use crate::services::shortener::normalize_url;
+
+const LINK_COLUMNS: &str =
+    "id, short_code, original_url, clicks, created_at, expires_at, owner, is_custom, password_hash, max_clicks, activates_at, expired_redirect_url, redirect_status, forward_query, forward_path, geo_targets, device_targets, schedule_targets, sticky_variants,
+    (SELECT json_group_array(json_object('url', d.url, 'weight', d.weight) ORDER BY d.position)
+        FROM link_destinations d WHERE d.link_id = links.id) AS destinations";
+
+/// Single-file backend for small, single-node deployments. Timestamps are
+/// stored as RFC 3339 text in UTC, so they compare correctly as strings.
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn connect(database_url: &str) -> Result<Self, AppError> {
+        let options = SqliteConnectOptions::from_str(database_url)?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .foreign_keys(true);
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(8)
+            .connect_with(options)
+            .await?;
+
+        sqlx::migrate!("./migrations/sqlite")
+            .run(&pool)
+            .await
+            .map_err(sqlx::Error::from)?;
+
+        Ok(Self::new(pool))
+    }
+}
+
+#[async_trait]
+impl LinkStore for SqliteStore {
+    async fn create_link(&self, link: &Link) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO links (id, short_code, original_url, clicks, created_at, expires_at, owner, normalized_url, is_custom, password_hash, max_clicks, activates_at, expired_redirect_url, redirect_status,
+                forward_query, forward_path, geo_targets, device_targets, schedule_targets, sticky_variants)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)
+            "#,
+        )
+        .bind(link.id)
+        .bind(&link.short_code)
+        .bind(&link.original_url)
+        .bind(link.clicks)
+        .bind(link.created_at)
+        .bind(link.expires_at)
+        .bind(&link.owner)
+        .bind(normalize_url(&link.original_url))
+        .bind(link.is_custom)
+        .bind(&link.password_hash)
+        .bind(link.max_clicks)
+        .bind(link.activates_at)
+        .bind(&link.expired_redirect_url)
+        .bind(link.redirect_status)
+        .bind(link.forward_query)
+        .bind(link.forward_path)
+        .bind(&link.geo_targets)
+        .bind(&link.device_targets)
+        .bind(&link.schedule_targets)
+        .bind(link.sticky_variants)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => AppError::Conflict,
+            _ => AppError::Database(e),
+        })?;
+
+        for (position, destination) in link.destinations.iter().enumerate() {
+            sqlx::query("INSERT INTO link_destinations (link_id, position, url, weight) VALUES (?1, ?2, ?3, ?4)")
+                .bind(link.id)
+                .bind(position as i64)
+                .bind(&destination.url)
+                .bind(destination.weight)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn find_link_by_code(&self, short_code: &str) -> Result<Option<Link>, AppError> {
+        let link = sqlx::query_as::<_, Link>(&format!("SELECT {} FROM links WHERE short_code = ?1", LINK_COLUMNS))
+            .bind(short_code)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(link)
+    }
+
+    async fn list_links(&self, limit: i64, offset: i64) -> Result<Vec<Link>, AppError> {
+        let links = sqlx::query_as::<_, Link>(&format!(
+            "SELECT {} FROM links ORDER BY created_at DESC, short_code LIMIT ?1 OFFSET ?2",
+            LINK_COLUMNS
+        ))
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(links)
+    }
+
+    async fn update_link(
+        &self,
+        short_code: &str,
+        original_url: &str,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<Option<Link>, AppError> {
+        let link = sqlx::query_as::<_, Link>(&format!(
+            r#"
+            UPDATE links
+            SET original_url = ?2, expires_at = ?3, normalized_url = ?4
+            WHERE short_code = ?1
+            RETURNING {}
+            "#,
+            LINK_COLUMNS
+        ))
+        .bind(short_code)
+        .bind(original_url)
+        .bind(expires_at)
+        .bind(normalize_url(original_url))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    async fn find_duplicate_link(
+        &self,
+        original_url: &str,
+        owner: Option<&str>,
+        redirect_status: RedirectStatus,
+    ) -> Result<Option<Link>, AppError> {
+        let link = sqlx::query_as::<_, Link>(&format!(
+            r#"
+            SELECT {} FROM links
+            WHERE normalized_url = ?1
+            AND owner IS ?2
+            AND NOT is_custom
+            AND expires_at IS NULL
+            AND password_hash IS NULL
+            AND max_clicks IS NULL
+            AND activates_at IS NULL
+            AND expired_redirect_url IS NULL
+            AND NOT forward_query
+            AND NOT forward_path
+            AND redirect_status = ?3
+            AND geo_targets = '[]'
+            AND device_targets = '[]'
+            AND schedule_targets = '[]'
+            AND NOT EXISTS (SELECT 1 FROM link_destinations d WHERE d.link_id = links.id)
+            ORDER BY created_at
+            LIMIT 1
+            "#,
+            LINK_COLUMNS
+        ))
+        .bind(normalize_url(original_url))
+        .bind(owner)
+        .bind(redirect_status)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    async fn claim_idempotency_key(&self, key: &str, fingerprint: &str) -> Result<Option<IdempotencyRecord>, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let claimed: Option<(String,)> = sqlx::query_as(
+            r#"
+            INSERT INTO idempotency_keys (key, fingerprint, short_code, created_at)
+            VALUES (?1, ?2, NULL, ?3)
+            ON CONFLICT (key) DO UPDATE
+            SET fingerprint = excluded.fingerprint, short_code = NULL, created_at = excluded.created_at
+            WHERE idempotency_keys.created_at < ?4
+            RETURNING key
+            "#,
+        )
+        .bind(key)
+        .bind(fingerprint)
+        .bind(now)
+        .bind(now - time::Duration::hours(24))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if claimed.is_some() {
+            return Ok(None);
+        }
+
+        let existing: Option<(String, Option<String>)> =
+            sqlx::query_as("SELECT fingerprint, short_code FROM idempotency_keys WHERE key = ?1")
+                .bind(key)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(existing.map(|(fingerprint, short_code)| IdempotencyRecord { fingerprint, short_code }))
+    }
+
+    async fn complete_idempotency_key(&self, key: &str, short_code: &str) -> Result<(), AppError> {
+        sqlx::query("UPDATE idempotency_keys SET short_code = ?2 WHERE key = ?1")
+            .bind(key)
+            .bind(short_code)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, key: &str) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE key = ?1 AND short_code IS NULL")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_link(&self, short_code: &str) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM links WHERE short_code = ?1")
+            .bind(short_code)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn add_clicks(&self, short_codes: &[String], counts: &[i64]) -> Result<u64, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let mut updated = 0;
+
+        for (short_code, count) in short_codes.iter().zip(counts) {
+            updated += sqlx::query("UPDATE links SET clicks = clicks + ?2 WHERE short_code = ?1")
+                .bind(short_code)
+                .bind(count)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        }
+
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn consume_click(&self, short_code: &str) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE links
+            SET clicks = clicks + 1
+            WHERE short_code = ?1
+            AND (max_clicks IS NULL OR clicks < max_clicks)
+            "#,
+        )
+        .bind(short_code)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn record_click(&self, event: &ClickEvent) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO click_events (short_code, clicked_at, referrer, user_agent, ip_hash, country, matched_rule, variant)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            "#,
+        )
+        .bind(&event.short_code)
+        .bind(event.clicked_at)
+        .bind(&event.referrer)
+        .bind(&event.user_agent)
+        .bind(&event.ip_hash)
+        .bind(&event.country)
+        .bind(&event.matched_rule)
+        .bind(event.variant)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_analytics(&self, short_code: Option<&str>, since: OffsetDateTime) -> Result<AnalyticsResponse, AppError> {
+        const FILTER: &str = "(?1 IS NULL OR short_code = ?1) AND clicked_at >= ?2";
+
+        let (total_clicks, unique_clicks): (i64, i64) = sqlx::query_as(&format!(
+            "SELECT COUNT(*), COUNT(DISTINCT ip_hash) FROM click_events WHERE {}",
+            FILTER
+        ))
+        .bind(short_code)
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let clicks_by_date: Vec<(String, i64)> = sqlx::query_as(&format!(
+            "SELECT substr(clicked_at, 1, 10), COUNT(*) FROM click_events WHERE {} GROUP BY 1 ORDER BY 1",
+            FILTER
+        ))
+        .bind(short_code)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let clicks_by_country: Vec<(String, i64)> = sqlx::query_as(&format!(
+            r#"
+            SELECT COALESCE(country, 'Unknown'), COUNT(*) FROM click_events WHERE {}
+            GROUP BY 1 ORDER BY 2 DESC, 1 LIMIT ?3
+            "#,
+            FILTER
+        ))
+        .bind(short_code)
+        .bind(since)
+        .bind(BREAKDOWN_LIMIT as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let clicks_by_rule: Vec<(String, i64)> = sqlx::query_as(&format!(
+            r#"
+            SELECT COALESCE(matched_rule, 'default'), COUNT(*) FROM click_events WHERE {}
+            GROUP BY 1 ORDER BY 2 DESC, 1 LIMIT ?3
+            "#,
+            FILTER
+        ))
+        .bind(short_code)
+        .bind(since)
+        .bind(BREAKDOWN_LIMIT as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let referrers: Vec<(Option<String>, i64)> = sqlx::query_as(&format!(
+            "SELECT referrer, COUNT(*) FROM click_events WHERE {} GROUP BY 1",
+            FILTER
+        ))
+        .bind(short_code)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(AnalyticsResponse {
+            total_clicks,
+            unique_clicks,
+            clicks_by_date: clicks_by_date
+                .into_iter()
+                .map(|(date, clicks)| DateClicks { date, clicks })
+                .collect(),
+            clicks_by_country: clicks_by_country
+                .into_iter()
+                .map(|(country, clicks)| CountryClicks { country, clicks })
+                .collect(),
+            clicks_by_referrer: group_referrers(referrers),
+            clicks_by_rule: clicks_by_rule
+                .into_iter()
+                .map(|(rule, clicks)| RuleClicks { rule, clicks })
+                .collect(),
+            clicks_by_variant: vec![],
+            top_links: vec![],
+        })
+    }
+
+    async fn get_variant_clicks(&self, short_code: &str, since: OffsetDateTime) -> Result<Vec<(i64, i64)>, AppError> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT variant, COUNT(*) FROM click_events
+            WHERE short_code = ?1 AND clicked_at >= ?2 AND variant IS NOT NULL
+            GROUP BY 1 ORDER BY 1
+            "#,
+        )
+        .bind(short_code)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    async fn count_links(&self) -> Result<i64, AppError> {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM links")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn top_links_since(&self, since: OffsetDateTime, limit: i64) -> Result<Vec<TopLink>, AppError> {
+        let links: Vec<(String, String, i64)> = sqlx::query_as(
+            r#"
+            SELECT l.short_code, l.original_url, COUNT(*)
+            FROM click_events c
+            JOIN links l ON l.short_code = c.short_code
+            WHERE c.clicked_at >= ?1
+            GROUP BY l.short_code, l.original_url
+            ORDER BY 3 DESC, 1
+            LIMIT ?2
+            "#,
+        )
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(links
+            .into_iter()
+            .map(|(short_code, original_url, clicks)| TopLink { short_code, original_url, clicks })
+            .collect())
+    }
+
+    async fn get_links_by_codes(&self, short_codes: &[String]) -> Result<Vec<Link>, AppError> {
+        if short_codes.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new(format!("SELECT {} FROM links WHERE short_code IN (", LINK_COLUMNS));
+        let mut codes = query.separated(", ");
+        for short_code in short_codes {
+            codes.push_bind(short_code);
+        }
+        codes.push_unseparated(")");
+
+        let links = query.build_query_as::<Link>().fetch_all(&self.pool).await?;
+
+        Ok(links)
+    }
+
+    async fn next_code_sequence(&self) -> Result<u64, AppError> {
+        let (value,): (i64,) = sqlx::query_as("UPDATE link_code_seq SET value = value + 1 WHERE id = 1 RETURNING value")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(value as u64)
+    }
+
+    async fn short_code_exists(&self, short_code: &str) -> Result<bool, AppError> {
+        let (exists,): (bool,) = sqlx::query_as("SELECT EXISTS(SELECT 1 FROM links WHERE short_code = ?1)")
+            .bind(short_code)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(exists)
+    }
+}
@@ -0,0 +1,121 @@
+use url::form_urlencoded;
+use url::Url;
+
+use crate::error::AppError;
+use crate::models::link::CachedLink;
+
+/// Works out where a visitor goes, given the destination picked for them and
+/// whatever followed the short code.
+///
+/// A path suffix (`/abc/docs/intro`) is only accepted by links that forward
+/// paths; it is appended to the destination's path segment by segment, with
+/// `.` and `..` dropped so it can't climb out of it. Links that forward query
+/// strings append the incoming parameters to the destination's. When both
+/// name the same parameter the destination's value wins and the incoming one
+/// is dropped, so visitors can't override parameters set by the link's owner.
+pub fn resolve(link: &CachedLink, base: &str, suffix: Option<&str>, query: Option<&str>) -> Result<String, AppError> {
+    let suffix = suffix.filter(|suffix| !suffix.is_empty());
+    if suffix.is_some() && !link.forward_path {
+        return Err(AppError::NotFound);
+    }
+
+    let query = query.filter(|query| link.forward_query && !query.is_empty());
+    if suffix.is_none() && query.is_none() {
+        return Ok(base.to_string());
+    }
+
+    let mut destination = Url::parse(base).map_err(|_| AppError::InvalidUrl(base.to_string()))?;
+
+    if let Some(suffix) = suffix {
+        destination
+            .path_segments_mut()
+            .map_err(|_| AppError::InvalidUrl(base.to_string()))?
+            .pop_if_empty()
+            .extend(suffix.split('/').filter(|segment| !segment.is_empty()));
+    }
+
+    if let Some(query) = query {
+        let existing: Vec<String> = destination.query_pairs().map(|(name, _)| name.into_owned()).collect();
+        let mut incoming = form_urlencoded::Serializer::new(String::new());
+        let mut forwarded = false;
+        for (name, value) in form_urlencoded::parse(query.as_bytes()) {
+            if !existing.iter().any(|existing| *existing == name) {
+                incoming.append_pair(&name, &value);
+                forwarded = true;
+            }
+        }
+
+        if forwarded {
+            let incoming = incoming.finish();
+            // Append rather than rebuild, so the destination's own query keeps its exact encoding.
+            let merged = match destination.query().filter(|query| !query.is_empty()) {
+                Some(own) => format!("{}&{}", own, incoming),
+                None => incoming,
+            };
+            destination.set_query(Some(&merged));
+        }
+    }
+
+    Ok(destination.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(url: &str, forward_path: bool, forward_query: bool) -> CachedLink {
+        CachedLink { forward_path, forward_query, ..CachedLink::for_url(url) }
+    }
+
+    #[test]
+    fn test_plain_link_ignores_query() {
+        let link = link("https://example.com/a?b=1", false, false);
+        assert_eq!(resolve(&link, &link.url, None, Some("utm_source=x")).unwrap(), "https://example.com/a?b=1");
+    }
+
+    #[test]
+    fn test_suffix_requires_forward_path() {
+        let link = link("https://example.com", false, false);
+        assert!(matches!(resolve(&link, &link.url, Some("docs"), None), Err(AppError::NotFound)));
+        assert_eq!(resolve(&link, &link.url, Some(""), None).unwrap(), "https://example.com");
+    }
+
+    #[test]
+    fn test_forwards_path_suffix() {
+        let link = link("https://docs.example.com/", true, false);
+        assert_eq!(
+            resolve(&link, &link.url, Some("getting-started"), None).unwrap(),
+            "https://docs.example.com/getting-started"
+        );
+
+        let link = self::link("https://example.com/base?v=2", true, false);
+        assert_eq!(
+            resolve(&link, &link.url, Some("../a/./b c/"), None).unwrap(),
+            "https://example.com/base/a/b%20c?v=2"
+        );
+    }
+
+    #[test]
+    fn test_merges_query_with_destination_precedence() {
+        let link = link("https://example.com/?utm_source=owner&ref=a%20b", false, true);
+        assert_eq!(
+            resolve(&link, &link.url, None, Some("utm_source=visitor&utm_medium=email")).unwrap(),
+            "https://example.com/?utm_source=owner&ref=a%20b&utm_medium=email"
+        );
+
+        let link = self::link("https://example.com/landing", false, true);
+        assert_eq!(
+            resolve(&link, &link.url, None, Some("q=rust+lang")).unwrap(),
+            "https://example.com/landing?q=rust+lang"
+        );
+    }
+
+    #[test]
+    fn test_forwards_path_and_query_together() {
+        let link = link("https://example.com/docs#top", true, true);
+        assert_eq!(
+            resolve(&link, &link.url, Some("intro"), Some("lang=en")).unwrap(),
+            "https://example.com/docs/intro?lang=en#top"
+        );
+    }
+}
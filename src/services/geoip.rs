@@ -0,0 +1,39 @@
+use maxminddb::{path, MaxMindDbError, Reader};
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Country lookups against a MaxMind-format database (GeoLite2/GeoIP2 Country
+/// or City) loaded into memory at startup.
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoIp {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MaxMindDbError> {
+        Ok(Self {
+            reader: Reader::open_readfile(path)?,
+        })
+    }
+
+    /// ISO 3166-1 alpha-2 code of the country `ip` is registered in, or `None`
+    /// for addresses the database doesn't cover, such as private ranges.
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        let result = self.reader.lookup(ip).ok()?;
+        let iso_code: Option<String> = result.decode_path(&path!["country", "iso_code"]).ok()?;
+        iso_code.map(|code| code.to_ascii_uppercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_rejects_missing_and_invalid_files() {
+        assert!(GeoIp::open("/nonexistent/GeoLite2-Country.mmdb").is_err());
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"not a maxmind database").unwrap();
+        assert!(GeoIp::open(file.path()).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use std::time::Duration;
+use time::{macros::format_description, Date};
+
+use crate::services::cache::CacheStore;
+
+/// Longest analytics window served, and how long per-day click sets are kept.
+pub const MAX_WINDOW_DAYS: i64 = 365;
+
+const UNION_TTL: Duration = Duration::from_secs(60);
+
+pub fn day_key(date: Date) -> String {
+    let format = format_description!("[year]-[month]-[day]");
+    format!("leaderboard:{}", date.format(&format).unwrap_or_default())
+}
+
+pub fn window_keys(today: Date, days: i64) -> Vec<String> {
+    (0..days)
+        .filter_map(|offset| today.checked_sub(time::Duration::days(offset)))
+        .map(day_key)
+        .collect()
+}
+
+pub async fn record_click(cache: &dyn CacheStore, short_code: &str, today: Date) -> redis::RedisResult<()> {
+    let retention = Duration::from_secs((MAX_WINDOW_DAYS as u64 + 1) * 86400);
+    cache.zincr(&day_key(today), short_code, retention).await
+}
+
+/// Top links by clicks over the last `days` days including `today`. The union is
+/// memoised briefly so dashboard polling doesn't recompute it on every request.
+pub async fn top_links(
+    cache: &dyn CacheStore,
+    today: Date,
+    days: i64,
+    limit: usize,
+) -> redis::RedisResult<Vec<(String, i64)>> {
+    let dest = format!("{}:{}d", day_key(today), days);
+    cache.zunion_top(&dest, &window_keys(today, days), UNION_TTL, limit).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::date;
+
+    #[test]
+    fn test_day_key_format() {
+        assert_eq!(day_key(date!(2024 - 03 - 07)), "leaderboard:2024-03-07");
+    }
+
+    #[test]
+    fn test_window_keys_walks_back_from_today() {
+        let keys = window_keys(date!(2024 - 03 - 01), 3);
+
+        assert_eq!(
+            keys,
+            vec![
+                "leaderboard:2024-03-01",
+                "leaderboard:2024-02-29",
+                "leaderboard:2024-02-28",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_window_keys_empty_window() {
+        assert!(window_keys(date!(2024 - 03 - 01), 0).is_empty());
+    }
+}
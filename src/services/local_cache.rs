@@ -0,0 +1,131 @@
+use moka::{sync::Cache, Expiry};
+use std::time::{Duration, Instant};
+
+#[derive(Clone)]
+struct Entry {
+    value: String,
+    ttl: Duration,
+}
+
+struct EntryExpiry;
+
+impl Expiry<String, Entry> for EntryExpiry {
+    fn expire_after_create(&self, _key: &String, entry: &Entry, _created_at: Instant) -> Option<Duration> {
+        Some(entry.ttl)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        entry: &Entry,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(entry.ttl)
+    }
+}
+
+/// Bounded in-process tier in front of Redis. Each entry lives for the shorter
+/// of its own TTL and `max_ttl`, which caps how long an instance can serve a
+/// value that another instance has since changed or deleted.
+pub struct LocalCache {
+    entries: Option<Cache<String, Entry>>,
+    max_ttl: Duration,
+}
+
+impl LocalCache {
+    /// A `capacity` or `max_ttl` of zero disables the tier.
+    pub fn new(capacity: u64, max_ttl: Duration) -> Self {
+        let entries = (capacity > 0 && !max_ttl.is_zero()).then(|| {
+            Cache::builder()
+                .max_capacity(capacity)
+                .expire_after(EntryExpiry)
+                .build()
+        });
+
+        Self { entries, max_ttl }
+    }
+
+    pub fn disabled() -> Self {
+        Self::new(0, Duration::ZERO)
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.entries.as_ref()?.get(key).map(|entry| entry.value)
+    }
+
+    pub fn insert(&self, key: &str, value: &str, ttl: Duration) {
+        let Some(entries) = &self.entries else {
+            return;
+        };
+
+        let ttl = ttl.min(self.max_ttl);
+        if ttl.is_zero() {
+            entries.invalidate(key);
+            return;
+        }
+
+        entries.insert(
+            key.to_string(),
+            Entry {
+                value: value.to_string(),
+                ttl,
+            },
+        );
+    }
+
+    pub fn invalidate(&self, key: &str) {
+        if let Some(entries) = &self.entries {
+            entries.invalidate(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_get() {
+        let cache = LocalCache::new(10, Duration::from_secs(60));
+        cache.insert("abc", "https://example.com", Duration::from_secs(30));
+
+        assert_eq!(cache.get("abc").as_deref(), Some("https://example.com"));
+        assert_eq!(cache.get("missing"), None);
+    }
+
+    #[test]
+    fn test_entry_expires_with_its_own_ttl() {
+        let cache = LocalCache::new(10, Duration::from_secs(60));
+        cache.insert("abc", "https://example.com", Duration::from_millis(20));
+
+        std::thread::sleep(Duration::from_millis(40));
+        assert_eq!(cache.get("abc"), None);
+    }
+
+    #[test]
+    fn test_ttl_is_clamped_to_max_ttl() {
+        let cache = LocalCache::new(10, Duration::from_millis(20));
+        cache.insert("abc", "https://example.com", Duration::from_secs(3600));
+
+        std::thread::sleep(Duration::from_millis(40));
+        assert_eq!(cache.get("abc"), None);
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let cache = LocalCache::new(10, Duration::from_secs(60));
+        cache.insert("abc", "https://example.com", Duration::from_secs(30));
+        cache.invalidate("abc");
+
+        assert_eq!(cache.get("abc"), None);
+    }
+
+    #[test]
+    fn test_disabled_cache_stores_nothing() {
+        let cache = LocalCache::disabled();
+        cache.insert("abc", "https://example.com", Duration::from_secs(30));
+
+        assert_eq!(cache.get("abc"), None);
+    }
+}
@@ -1,3 +1,19 @@
+pub mod analytics;
 pub mod cache;
+pub mod click_counter;
+pub mod code_generator;
+pub mod code_policy;
+pub mod cron;
 pub mod db;
-pub mod shortener; 
\ No newline at end of file
+pub mod destination;
+pub mod geoip;
+pub mod leaderboard;
+pub mod local_cache;
+pub mod password;
+pub mod rotation;
+pub mod schedule;
+pub mod shortener;
+pub mod single_flight;
+pub mod targeting;
+pub mod url_policy;
+pub mod user_agent;
@@ -0,0 +1,131 @@
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::AppError;
+use crate::services::cache::CacheStore;
+
+pub const MAX_PASSWORD_LEN: usize = 128;
+
+pub fn validate_password(password: &str) -> Result<(), AppError> {
+    if password.is_empty() || password.chars().count() > MAX_PASSWORD_LEN {
+        return Err(AppError::InvalidRequest(format!(
+            "Password must be between 1 and {} characters",
+            MAX_PASSWORD_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Hashes `password` with Argon2id and a random salt, returning a PHC string.
+/// Deliberately slow; call it from a blocking task.
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| {
+            tracing::error!("Failed to hash link password: {}", e);
+            AppError::Internal
+        })
+}
+
+/// Checks `candidate` against a stored PHC string. Malformed hashes never match.
+pub fn verify_password(hash: &str, candidate: &str) -> bool {
+    let Ok(parsed) = PasswordHash::new(hash) else {
+        return false;
+    };
+    Argon2::default()
+        .verify_password(candidate.as_bytes(), &parsed)
+        .is_ok()
+}
+
+/// Counter key for unlock attempts on `short_code` in the fixed window that
+/// contains `now`. Each window gets its own key, so the count resets when the
+/// window rolls over.
+pub fn attempts_key(short_code: &str, window: Duration, now: SystemTime) -> String {
+    let elapsed = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let bucket = elapsed / window.as_secs().max(1);
+    format!("password_attempts:{}:{}", short_code, bucket)
+}
+
+/// Counts an unlock attempt against `key` before the password is checked, so
+/// parallel guesses can't all see the same count; `false` once `limit`
+/// attempts were made in the window. Counting fails open: without a cache the
+/// Argon2 cost is the only brake.
+pub async fn claim_attempt(cache: &dyn CacheStore, key: &str, limit: i64, window: Duration) -> bool {
+    cache
+        .incr_with_ttl(key, 1, window)
+        .await
+        .map_or(true, |attempts| attempts <= limit)
+}
+
+/// Hands back the attempt a correct password claimed, so visitors who know
+/// the password never use up the limit for others.
+pub async fn release_attempt(cache: &dyn CacheStore, key: &str, window: Duration) {
+    let _ = cache.incr_with_ttl(key, -1, window).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::cache::MemoryCache;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_hash_and_verify() {
+        let hash = hash_password("hunter2").unwrap();
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password(&hash, "hunter2"));
+        assert!(!verify_password(&hash, "hunter3"));
+    }
+
+    #[test]
+    fn test_verify_rejects_malformed_hash() {
+        assert!(!verify_password("not a hash", "hunter2"));
+    }
+
+    #[test]
+    fn test_validate_password_length() {
+        assert!(validate_password("").is_err());
+        assert!(validate_password("x").is_ok());
+        assert!(validate_password(&"x".repeat(MAX_PASSWORD_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_attempts_key_is_per_window() {
+        let window = Duration::from_secs(300);
+        let start = UNIX_EPOCH + Duration::from_secs(3000);
+
+        assert_eq!(attempts_key("abc", window, start), "password_attempts:abc:10");
+        assert_eq!(attempts_key("abc", window, start + Duration::from_secs(299)), "password_attempts:abc:10");
+        assert_eq!(attempts_key("abc", window, start + Duration::from_secs(300)), "password_attempts:abc:11");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_claim_attempt_limits_concurrent_guesses() {
+        let cache = Arc::new(MemoryCache::new(100, Duration::from_secs(60)));
+        let window = Duration::from_secs(60);
+
+        let mut guesses = tokio::task::JoinSet::new();
+        for _ in 0..20 {
+            let cache = cache.clone();
+            guesses.spawn(async move { claim_attempt(cache.as_ref(), "attempts", 5, window).await });
+        }
+        let allowed = guesses.join_all().await.into_iter().filter(|&allowed| allowed).count();
+
+        assert_eq!(allowed, 5);
+    }
+
+    #[tokio::test]
+    async fn test_release_attempt_returns_claim() {
+        let cache = MemoryCache::new(100, Duration::from_secs(60));
+        let window = Duration::from_secs(60);
+
+        assert!(claim_attempt(&cache, "attempts", 1, window).await);
+        release_attempt(&cache, "attempts", window).await;
+        assert!(claim_attempt(&cache, "attempts", 1, window).await);
+        assert!(!claim_attempt(&cache, "attempts", 1, window).await);
+    }
+}
@@ -0,0 +1,161 @@
+use axum::http::{header::COOKIE, HeaderMap};
+use std::time::Duration;
+
+use crate::error::AppError;
+use crate::models::link::Destination;
+
+pub const MAX_DESTINATIONS: usize = 10;
+pub const MAX_WEIGHT: u32 = 10_000;
+
+/// Picks a variant with probability proportional to its weight, given `roll`
+/// uniform in `[0, 1)`. `None` when the link doesn't rotate.
+pub fn pick(destinations: &[Destination], roll: f64) -> Option<usize> {
+    let total: u64 = destinations.iter().map(|destination| u64::from(destination.weight)).sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut remaining = ((roll * total as f64) as u64).min(total - 1);
+    for (index, destination) in destinations.iter().enumerate() {
+        let weight = u64::from(destination.weight);
+        if remaining < weight {
+            return Some(index);
+        }
+        remaining -= weight;
+    }
+
+    None
+}
+
+/// Checks an A/B split: either no destinations, or two to `MAX_DESTINATIONS`
+/// with weights between 1 and `MAX_WEIGHT`. Destination URLs are validated by
+/// the caller like any other URL.
+pub fn validate_destinations(destinations: &[Destination], sticky: bool) -> Result<(), AppError> {
+    if destinations.is_empty() {
+        if sticky {
+            return Err(AppError::InvalidRequest("sticky_variants requires destinations".to_string()));
+        }
+        return Ok(());
+    }
+
+    if !(2..=MAX_DESTINATIONS).contains(&destinations.len()) {
+        return Err(AppError::InvalidRequest(format!(
+            "A/B rotation needs between 2 and {} destinations",
+            MAX_DESTINATIONS
+        )));
+    }
+
+    if destinations.iter().any(|destination| !(1..=MAX_WEIGHT).contains(&destination.weight)) {
+        return Err(AppError::InvalidRequest(format!(
+            "Destination weights must be between 1 and {}",
+            MAX_WEIGHT
+        )));
+    }
+
+    Ok(())
+}
+
+/// Per-link cookie holding the variant a visitor was assigned. Short codes
+/// only use characters that are valid in cookie names.
+pub fn cookie_name(short_code: &str) -> String {
+    format!("lv_{}", short_code)
+}
+
+/// The variant remembered in the visitor's cookie for `short_code`, if any.
+/// Callers must still check it against the link's current destinations.
+pub fn assigned_variant(headers: &HeaderMap, short_code: &str) -> Option<usize> {
+    let name = cookie_name(short_code);
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+/// `Set-Cookie` value pinning `variant`, scoped to the short URL so each link
+/// keeps its own assignment.
+pub fn variant_cookie(short_code: &str, variant: usize, max_age: Duration, secure: bool) -> String {
+    format!(
+        "{}={}; Path=/{}; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        cookie_name(short_code),
+        variant,
+        short_code,
+        max_age.as_secs(),
+        if secure { "; Secure" } else { "" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn destinations(weights: &[u32]) -> Vec<Destination> {
+        weights
+            .iter()
+            .enumerate()
+            .map(|(index, &weight)| Destination {
+                url: format!("https://example.com/{}", index),
+                weight,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pick_follows_weights() {
+        let split = destinations(&[70, 30]);
+
+        assert_eq!(pick(&split, 0.0), Some(0));
+        assert_eq!(pick(&split, 0.69), Some(0));
+        assert_eq!(pick(&split, 0.70), Some(1));
+        assert_eq!(pick(&split, 0.999_999), Some(1));
+        assert_eq!(pick(&[], 0.5), None);
+    }
+
+    #[test]
+    fn test_pick_distribution() {
+        let split = destinations(&[3, 1]);
+        let mut counts = [0; 2];
+        for step in 0..1000 {
+            counts[pick(&split, step as f64 / 1000.0).unwrap()] += 1;
+        }
+
+        assert_eq!(counts, [750, 250]);
+    }
+
+    #[test]
+    fn test_validate_destinations() {
+        assert!(validate_destinations(&[], false).is_ok());
+        assert!(validate_destinations(&[], true).is_err());
+        assert!(validate_destinations(&destinations(&[50, 50]), true).is_ok());
+        assert!(validate_destinations(&destinations(&[100]), false).is_err());
+        assert!(validate_destinations(&destinations(&[1, 0]), false).is_err());
+        assert!(validate_destinations(&destinations(&[1, MAX_WEIGHT + 1]), false).is_err());
+        assert!(validate_destinations(&destinations(&[1; MAX_DESTINATIONS + 1]), false).is_err());
+    }
+
+    #[test]
+    fn test_assigned_variant_reads_own_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.insert(COOKIE, HeaderValue::from_static("theme=dark; lv_abc=1; lv_abcd=0"));
+
+        assert_eq!(assigned_variant(&headers, "abc"), Some(1));
+        assert_eq!(assigned_variant(&headers, "abcd"), Some(0));
+        assert_eq!(assigned_variant(&headers, "xyz"), None);
+
+        headers.insert(COOKIE, HeaderValue::from_static("lv_abc=first"));
+        assert_eq!(assigned_variant(&headers, "abc"), None);
+    }
+
+    #[test]
+    fn test_variant_cookie() {
+        assert_eq!(
+            variant_cookie("abc", 2, Duration::from_secs(60), true),
+            "lv_abc=2; Path=/abc; Max-Age=60; HttpOnly; SameSite=Lax; Secure"
+        );
+        assert!(!variant_cookie("abc", 0, Duration::from_secs(60), false).contains("Secure"));
+    }
+}
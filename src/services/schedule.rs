@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use time_tz::{timezones, OffsetDateTimeExt, Tz};
+
+use crate::error::AppError;
+use crate::models::link::ScheduleTarget;
+use crate::services::cron::CronExpr;
+
+pub const MAX_SCHEDULE_TARGETS: usize = 20;
+
+/// A `ScheduleTarget` with its cron expression and timezone resolved when the
+/// cached link is built, so redirects only match against them. It is stored
+/// in the cache as the plain target. Rules that no longer parse are kept but
+/// never active.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "ScheduleTarget", into = "ScheduleTarget")]
+pub struct Schedule {
+    pub target: ScheduleTarget,
+    window: Option<(CronExpr, &'static Tz)>,
+}
+
+impl Schedule {
+    /// Whether `now` falls inside the window, in the target's own timezone.
+    pub fn is_active(&self, now: OffsetDateTime) -> bool {
+        self.window
+            .as_ref()
+            .is_some_and(|(cron, timezone)| cron.matches(now.to_timezone(*timezone)))
+    }
+}
+
+impl From<ScheduleTarget> for Schedule {
+    fn from(target: ScheduleTarget) -> Self {
+        let window = match (target.cron.parse::<CronExpr>(), timezones::get_by_name(&target.timezone)) {
+            (Ok(cron), Some(timezone)) => Some((cron, timezone)),
+            _ => None,
+        };
+        Self { target, window }
+    }
+}
+
+impl From<Schedule> for ScheduleTarget {
+    fn from(schedule: Schedule) -> Self {
+        schedule.target
+    }
+}
+
+/// Checks that every cron expression and timezone parses. Destination URLs
+/// are validated by the caller like any other URL.
+pub fn validate_schedule_targets(targets: &[ScheduleTarget]) -> Result<(), AppError> {
+    if targets.len() > MAX_SCHEDULE_TARGETS {
+        return Err(AppError::InvalidRequest(format!(
+            "At most {} schedule targets are allowed",
+            MAX_SCHEDULE_TARGETS
+        )));
+    }
+
+    for target in targets {
+        if let Err(e) = target.cron.parse::<CronExpr>() {
+            return Err(AppError::InvalidRequest(format!("Invalid cron expression {:?}: {}", target.cron, e)));
+        }
+        if timezones::get_by_name(&target.timezone).is_none() {
+            return Err(AppError::InvalidRequest(format!(
+                "Unknown timezone {:?}, expected an IANA name such as Europe/Berlin",
+                target.timezone
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn target(cron: &str, timezone: &str) -> ScheduleTarget {
+        ScheduleTarget {
+            cron: cron.to_string(),
+            timezone: timezone.to_string(),
+            url: "https://example.com/chat".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_business_hours_in_local_time() {
+        let hours = Schedule::from(target("* 9-17 * * MON-FRI", "Europe/Berlin"));
+
+        // Wednesday; Berlin is UTC+2 in summer and UTC+1 in winter.
+        assert!(!hours.is_active(datetime!(2025-07-02 6:59 UTC)));
+        assert!(hours.is_active(datetime!(2025-07-02 7:00 UTC)));
+        assert!(hours.is_active(datetime!(2025-07-02 15:59:59.5 UTC)));
+        assert!(!hours.is_active(datetime!(2025-07-02 16:00 UTC)));
+        assert!(hours.is_active(datetime!(2025-01-08 16:30 UTC)));
+
+        // Saturday.
+        assert!(!hours.is_active(datetime!(2025-07-05 10:00 UTC)));
+    }
+
+    #[test]
+    fn test_invalid_rules_never_match() {
+        assert!(!Schedule::from(target("* * * * *", "Mars/Olympus")).is_active(datetime!(2025-07-02 10:00 UTC)));
+        assert!(!Schedule::from(target("not cron", "UTC")).is_active(datetime!(2025-07-02 10:00 UTC)));
+        assert!(Schedule::from(target("* * * * *", "UTC")).is_active(datetime!(2025-07-02 10:00 UTC)));
+    }
+
+    #[test]
+    fn test_validate_schedule_targets() {
+        assert!(validate_schedule_targets(&[target("30-59 8 * * 1-5", "America/New_York")]).is_ok());
+        assert!(validate_schedule_targets(&[target("* 25 * * *", "UTC")]).is_err());
+        assert!(validate_schedule_targets(&[target("0 * 9-17 * * *", "UTC")]).is_err());
+        assert!(validate_schedule_targets(&[target("* * * * *", "Nowhere/City")]).is_err());
+
+        let too_many = vec![target("* * * * *", "UTC"); MAX_SCHEDULE_TARGETS + 1];
+        assert!(validate_schedule_targets(&too_many).is_err());
+    }
+}
@@ -1,9 +1,12 @@
 use sha2::{Sha256, Digest};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
 
 const BASE62_CHARS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
 
+pub const DEFAULT_ALPHABET: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
 pub fn generate_short_code(url: &str, salt: &[u8]) -> String {
     let mut hasher = Sha256::new();
     hasher.update(url.as_bytes());
@@ -20,7 +23,7 @@ pub fn generate_short_code_with_timestamp(url: &str) -> String {
     
     let mut hasher = Sha256::new();
     hasher.update(url.as_bytes());
-    hasher.update(&timestamp.to_be_bytes());
+    hasher.update(timestamp.to_be_bytes());
     let result = hasher.finalize();
     URL_SAFE_NO_PAD.encode(&result[..6])
 }
@@ -38,6 +41,49 @@ pub fn generate_short_code_base62(url: &str) -> String {
     encode_base62(num)
 }
 
+/// Derives a fixed-length code from `url` and `salt` over `alphabet`. Each salt
+/// yields an unrelated code, so callers retry with a fresh salt when an insert
+/// collides with an existing code.
+pub fn generate_short_code_salted(url: &str, salt: u64, alphabet: &[u8], length: usize) -> String {
+    let base = alphabet.len() as u128;
+    let chars_per_block = (128.0 / (base as f64).log2()).floor() as usize;
+
+    let mut code = String::with_capacity(length);
+    let mut block = 0u32;
+    while code.len() < length {
+        let mut hasher = Sha256::new();
+        hasher.update(url.as_bytes());
+        hasher.update(salt.to_be_bytes());
+        hasher.update(block.to_be_bytes());
+        let result = hasher.finalize();
+
+        let mut num = u128::from_be_bytes(result[..16].try_into().unwrap());
+        for _ in 0..chars_per_block.min(length - code.len()) {
+            code.push(alphabet[(num % base) as usize] as char);
+            num /= base;
+        }
+        block += 1;
+    }
+
+    code
+}
+
+/// Canonical form of a destination used to detect duplicates: scheme and host
+/// lowercased, default ports dropped and an empty path written as `/`.
+pub fn normalize_url(url: &str) -> String {
+    Url::parse(url)
+        .map(|parsed| parsed.to_string())
+        .unwrap_or_else(|_| url.to_string())
+}
+
+/// An alphabet is usable when it has at least two distinct characters, all of
+/// which are also accepted in custom codes.
+pub fn is_valid_alphabet(alphabet: &str) -> bool {
+    let mut seen = std::collections::HashSet::new();
+    alphabet.len() >= 2
+        && alphabet.chars().all(|c| (c.is_ascii_alphanumeric() || c == '_' || c == '-') && seen.insert(c))
+}
+
 fn encode_base62(mut num: u64) -> String {
     if num == 0 {
         return "0".to_string();
@@ -60,6 +106,7 @@ pub fn is_valid_custom_code(code: &str) -> bool {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
     use rstest::rstest;
@@ -111,6 +158,52 @@ mod tests {
         assert!(code.chars().all(|c| BASE62_CHARS.contains(&(c as u8))));
     }
 
+    #[rstest]
+    #[case(4)]
+    #[case(7)]
+    #[case(11)]
+    #[case(32)]
+    fn test_generate_short_code_salted_length(#[case] length: usize) {
+        let code = generate_short_code_salted("https://example.com", 0, DEFAULT_ALPHABET.as_bytes(), length);
+
+        assert_eq!(code.len(), length);
+        assert!(code.bytes().all(|c| DEFAULT_ALPHABET.as_bytes().contains(&c)));
+    }
+
+    #[test]
+    fn test_generate_short_code_salted_salts_differ() {
+        let alphabet = DEFAULT_ALPHABET.as_bytes();
+        let first = generate_short_code_salted("https://example.com", 0, alphabet, 7);
+
+        assert_eq!(first, generate_short_code_salted("https://example.com", 0, alphabet, 7));
+        assert_ne!(first, generate_short_code_salted("https://example.com", 1, alphabet, 7));
+    }
+
+    #[test]
+    fn test_generate_short_code_salted_custom_alphabet() {
+        let code = generate_short_code_salted("https://example.com", 0, b"ab", 16);
+        assert!(code.chars().all(|c| c == 'a' || c == 'b'));
+    }
+
+    #[rstest]
+    #[case("HTTPS://Example.COM", "https://example.com/")]
+    #[case("https://example.com:443/a?b=c", "https://example.com/a?b=c")]
+    #[case("http://example.com:8080/Path", "http://example.com:8080/Path")]
+    #[case("not a url", "not a url")]
+    fn test_normalize_url(#[case] url: &str, #[case] expected: &str) {
+        assert_eq!(normalize_url(url), expected);
+    }
+
+    #[rstest]
+    #[case(DEFAULT_ALPHABET, true)]
+    #[case("abc-_", true)]
+    #[case("a", false)]
+    #[case("aab", false)]
+    #[case("ab/", false)]
+    fn test_is_valid_alphabet(#[case] alphabet: &str, #[case] expected: bool) {
+        assert_eq!(is_valid_alphabet(alphabet), expected);
+    }
+
     #[test]
     fn test_encode_base62() {
         assert_eq!(encode_base62(0), "0");
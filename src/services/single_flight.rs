@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Coalesces concurrent loads of the same key so only one runs at a time; every
+/// caller that arrives while it is in flight receives a clone of its result.
+///
+/// Errors are not shared: a failed load leaves the slot empty and the next
+/// waiter retries, so each caller still sees its own error.
+pub struct SingleFlight<V> {
+    inflight: Mutex<HashMap<String, Arc<OnceCell<V>>>>,
+}
+
+impl<V: Clone> SingleFlight<V> {
+    pub fn new() -> Self {
+        Self {
+            inflight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn run<F, Fut, E>(&self, key: &str, load: F) -> Result<V, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        let cell = {
+            let mut inflight = self.inflight.lock().unwrap();
+            inflight
+                .entry(key.to_string())
+                .or_insert_with(|| Arc::new(OnceCell::new()))
+                .clone()
+        };
+
+        let result = cell.get_or_try_init(load).await.cloned();
+
+        let mut inflight = self.inflight.lock().unwrap();
+        if inflight.get(key).is_some_and(|current| Arc::ptr_eq(current, &cell)) {
+            inflight.remove(key);
+        }
+
+        result
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.inflight.lock().unwrap().len()
+    }
+}
+
+impl<V: Clone> Default for SingleFlight<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_concurrent_calls_share_one_load() {
+        let flight = Arc::new(SingleFlight::<u32>::new());
+        let loads = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..16)
+            .map(|_| {
+                let flight = flight.clone();
+                let loads = loads.clone();
+                tokio::spawn(async move {
+                    flight
+                        .run("abc", || async {
+                            loads.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                            Ok::<_, ()>(42)
+                        })
+                        .await
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), Ok(42));
+        }
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(flight.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_sequential_calls_load_again() {
+        let flight = SingleFlight::<u32>::new();
+
+        assert_eq!(flight.run("abc", || async { Ok::<_, ()>(1) }).await, Ok(1));
+        assert_eq!(flight.run("abc", || async { Ok::<_, ()>(2) }).await, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_not_cached() {
+        let flight = SingleFlight::<u32>::new();
+
+        assert_eq!(flight.run("abc", || async { Err::<u32, _>("boom") }).await, Err("boom"));
+        assert_eq!(flight.run("abc", || async { Ok::<_, &str>(7) }).await, Ok(7));
+        assert_eq!(flight.in_flight(), 0);
+    }
+}
@@ -0,0 +1,305 @@
+use std::fmt;
+use time::OffsetDateTime;
+
+use crate::error::AppError;
+use crate::models::link::{CachedLink, DeviceKind, DeviceTarget, GeoTarget, Platform};
+use crate::services::rotation;
+
+pub const MAX_GEO_TARGETS: usize = 50;
+pub const MAX_DEVICE_TARGETS: usize = 20;
+
+/// What is known about the visitor when choosing a destination.
+#[derive(Debug, Clone, Default)]
+pub struct Visitor {
+    /// ISO 3166-1 alpha-2 code, when the GeoIP database knows the address.
+    pub country: Option<String>,
+    /// Read from the `User-Agent` header.
+    pub device: Option<DeviceKind>,
+    pub platform: Option<Platform>,
+    /// A/B variant remembered from an earlier visit.
+    pub variant: Option<usize>,
+}
+
+/// The rule that picked a destination, by its position in the link's list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchedRule {
+    Schedule(usize),
+    Device(usize),
+    Geo(usize),
+    Default,
+}
+
+impl fmt::Display for MatchedRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Schedule(index) => write!(f, "schedule:{}", index),
+            Self::Device(index) => write!(f, "device:{}", index),
+            Self::Geo(index) => write!(f, "geo:{}", index),
+            Self::Default => f.write_str("default"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Choice<'a> {
+    pub url: &'a str,
+    pub rule: MatchedRule,
+    /// Position of the A/B destination, when the default was split.
+    pub variant: Option<usize>,
+}
+
+/// Picks the destination for `visitor` at `now`. Schedule targets whose
+/// window is open come first, then device targets, so app store links apply
+/// everywhere; then the first geo target listing their country; otherwise the
+/// link's own URL, which is also where visitors of unknown device and
+/// location go. Links with A/B destinations replace that default with a
+/// weighted pick using `roll`, or the visitor's earlier variant when the link
+/// is sticky.
+pub fn choose<'a>(link: &'a CachedLink, visitor: &Visitor, now: OffsetDateTime, roll: f64) -> Choice<'a> {
+    if let Some((index, schedule)) = link
+        .schedule_targets
+        .iter()
+        .enumerate()
+        .find(|(_, schedule)| schedule.is_active(now))
+    {
+        return Choice { url: &schedule.target.url, rule: MatchedRule::Schedule(index), variant: None };
+    }
+
+    if let Some((index, target)) = link
+        .device_targets
+        .iter()
+        .enumerate()
+        .find(|(_, target)| device_matches(target, visitor))
+    {
+        return Choice { url: &target.url, rule: MatchedRule::Device(index), variant: None };
+    }
+
+    let geo = visitor.country.as_deref().and_then(|country| {
+        link.geo_targets
+            .iter()
+            .enumerate()
+            .find(|(_, target)| target.countries.iter().any(|code| code == country))
+    });
+
+    if let Some((index, target)) = geo {
+        return Choice { url: &target.url, rule: MatchedRule::Geo(index), variant: None };
+    }
+
+    let remembered = visitor
+        .variant
+        .filter(|&variant| link.sticky_variants && variant < link.destinations.len());
+    match remembered.or_else(|| rotation::pick(&link.destinations, roll)) {
+        Some(variant) => Choice { url: &link.destinations[variant].url, rule: MatchedRule::Default, variant: Some(variant) },
+        None => Choice { url: &link.url, rule: MatchedRule::Default, variant: None },
+    }
+}
+
+fn device_matches(target: &DeviceTarget, visitor: &Visitor) -> bool {
+    target.device.is_none_or(|device| visitor.device == Some(device))
+        && target.os.is_none_or(|os| visitor.platform == Some(os))
+}
+
+/// Checks the shape of geo targets and upper-cases their country codes so
+/// they compare equal to lookup results. Destination URLs are validated by
+/// the caller like any other URL.
+pub fn normalize_geo_targets(targets: &mut [GeoTarget]) -> Result<(), AppError> {
+    if targets.len() > MAX_GEO_TARGETS {
+        return Err(AppError::InvalidRequest(format!(
+            "At most {} geo targets are allowed",
+            MAX_GEO_TARGETS
+        )));
+    }
+
+    for target in targets.iter_mut() {
+        if target.countries.is_empty() {
+            return Err(AppError::InvalidRequest("Every geo target needs at least one country".to_string()));
+        }
+
+        for code in target.countries.iter_mut() {
+            if code.len() != 2 || !code.bytes().all(|byte| byte.is_ascii_alphabetic()) {
+                return Err(AppError::InvalidRequest(format!(
+                    "Invalid country code {:?}, expected ISO 3166-1 alpha-2",
+                    code
+                )));
+            }
+            code.make_ascii_uppercase();
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the shape of device targets; as with geo targets, destination URLs
+/// are validated by the caller.
+pub fn validate_device_targets(targets: &[DeviceTarget]) -> Result<(), AppError> {
+    if targets.len() > MAX_DEVICE_TARGETS {
+        return Err(AppError::InvalidRequest(format!(
+            "At most {} device targets are allowed",
+            MAX_DEVICE_TARGETS
+        )));
+    }
+
+    if targets.iter().any(|target| target.device.is_none() && target.os.is_none()) {
+        return Err(AppError::InvalidRequest("Every device target needs a device or an os".to_string()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::link::{Destination, ScheduleTarget};
+    use crate::services::schedule::Schedule;
+    use time::macros::datetime;
+
+    /// A Wednesday.
+    const NOW: OffsetDateTime = datetime!(2025-07-02 10:00 UTC);
+
+    fn target(countries: &[&str], url: &str) -> GeoTarget {
+        GeoTarget {
+            countries: countries.iter().map(|code| code.to_string()).collect(),
+            url: url.to_string(),
+        }
+    }
+
+    fn device_target(device: Option<DeviceKind>, os: Option<Platform>, url: &str) -> DeviceTarget {
+        DeviceTarget { device, os, url: url.to_string() }
+    }
+
+    fn link(geo_targets: Vec<GeoTarget>) -> CachedLink {
+        CachedLink { geo_targets, ..CachedLink::for_url("https://example.com") }
+    }
+
+    fn visitor(country: Option<&str>) -> Visitor {
+        Visitor {
+            country: country.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn device(device: Option<DeviceKind>, platform: Option<Platform>) -> Visitor {
+        Visitor { country: None, device, platform, variant: None }
+    }
+
+    #[test]
+    fn test_choose_first_matching_target() {
+        let link = link(vec![
+            target(&["DE", "AT"], "https://example.de"),
+            target(&["DE"], "https://example.com/de-second"),
+            target(&["FR"], "https://example.fr"),
+        ]);
+
+        assert_eq!(choose(&link, &visitor(Some("AT")), NOW, 0.0).url, "https://example.de");
+        assert_eq!(choose(&link, &visitor(Some("DE")), NOW, 0.0).rule, MatchedRule::Geo(0));
+        assert_eq!(choose(&link, &visitor(Some("FR")), NOW, 0.0).url, "https://example.fr");
+    }
+
+    #[test]
+    fn test_choose_falls_back_to_link_url() {
+        let link = link(vec![target(&["DE"], "https://example.de")]);
+
+        assert_eq!(choose(&link, &visitor(Some("US")), NOW, 0.0).url, "https://example.com");
+        assert_eq!(choose(&link, &visitor(None), NOW, 0.0), Choice { url: "https://example.com", rule: MatchedRule::Default, variant: None });
+    }
+
+    #[test]
+    fn test_choose_device_targets_in_order_before_geo() {
+        let link = CachedLink {
+            device_targets: vec![
+                device_target(None, Some(Platform::Ios), "https://apps.apple.com/app/id1"),
+                device_target(Some(DeviceKind::Mobile), Some(Platform::Android), "https://play.google.com/store/apps/details?id=app"),
+                device_target(Some(DeviceKind::Desktop), None, "https://example.com/desktop"),
+            ],
+            ..link(vec![target(&["DE"], "https://example.de")])
+        };
+
+        let iphone = Visitor { country: Some("DE".to_string()), ..device(Some(DeviceKind::Mobile), Some(Platform::Ios)) };
+        assert_eq!(choose(&link, &iphone, NOW, 0.0), Choice { url: "https://apps.apple.com/app/id1", rule: MatchedRule::Device(0), variant: None });
+        assert_eq!(choose(&link, &device(Some(DeviceKind::Mobile), Some(Platform::Android)), NOW, 0.0).rule, MatchedRule::Device(1));
+        assert_eq!(choose(&link, &device(Some(DeviceKind::Desktop), Some(Platform::Macos)), NOW, 0.0).rule, MatchedRule::Device(2));
+
+        let android_tablet = Visitor { country: Some("DE".to_string()), ..device(Some(DeviceKind::Tablet), Some(Platform::Android)) };
+        assert_eq!(choose(&link, &android_tablet, NOW, 0.0).rule, MatchedRule::Geo(0));
+        assert_eq!(choose(&link, &device(None, None), NOW, 0.0).rule, MatchedRule::Default);
+    }
+
+    #[test]
+    fn test_choose_rotates_default_destination() {
+        let split = CachedLink {
+            destinations: vec![
+                Destination { url: "https://example.com/a".to_string(), weight: 70 },
+                Destination { url: "https://example.com/b".to_string(), weight: 30 },
+            ],
+            ..link(vec![target(&["DE"], "https://example.de")])
+        };
+
+        assert_eq!(choose(&split, &visitor(None), NOW, 0.5), Choice { url: "https://example.com/a", rule: MatchedRule::Default, variant: Some(0) });
+        assert_eq!(choose(&split, &visitor(None), NOW, 0.9).variant, Some(1));
+        assert_eq!(choose(&split, &visitor(Some("DE")), NOW, 0.9).variant, None);
+
+        let returning = Visitor { variant: Some(1), ..visitor(None) };
+        assert_eq!(choose(&split, &returning, NOW, 0.5).variant, Some(0));
+
+        let sticky = CachedLink { sticky_variants: true, ..split };
+        assert_eq!(choose(&sticky, &returning, NOW, 0.5).url, "https://example.com/b");
+        let stale = Visitor { variant: Some(5), ..visitor(None) };
+        assert_eq!(choose(&sticky, &stale, NOW, 0.5).variant, Some(0));
+    }
+
+    #[test]
+    fn test_choose_open_schedule_window_first() {
+        let schedule = |cron: &str, url: &str| Schedule::from(ScheduleTarget {
+            cron: cron.to_string(),
+            timezone: "Europe/Berlin".to_string(),
+            url: url.to_string(),
+        });
+        let support = CachedLink {
+            schedule_targets: vec![
+                schedule("* 9-17 * * MON-FRI", "https://example.com/chat"),
+                schedule("* * * * *", "https://example.com/always"),
+            ],
+            device_targets: vec![device_target(None, Some(Platform::Ios), "https://apps.apple.com/app/id1")],
+            ..link(vec![])
+        };
+        let iphone = device(Some(DeviceKind::Mobile), Some(Platform::Ios));
+
+        assert_eq!(choose(&support, &iphone, NOW, 0.0), Choice { url: "https://example.com/chat", rule: MatchedRule::Schedule(0), variant: None });
+        assert_eq!(choose(&support, &iphone, datetime!(2025-07-02 20:00 UTC), 0.0).rule, MatchedRule::Schedule(1));
+
+        let after_hours = CachedLink { schedule_targets: support.schedule_targets[..1].to_vec(), ..support.clone() };
+        assert_eq!(choose(&after_hours, &iphone, datetime!(2025-07-05 10:00 UTC), 0.0).rule, MatchedRule::Device(0));
+        assert_eq!(choose(&after_hours, &visitor(None), datetime!(2025-07-05 10:00 UTC), 0.0).url, "https://example.com");
+    }
+
+    #[test]
+    fn test_matched_rule_labels() {
+        assert_eq!(MatchedRule::Schedule(1).to_string(), "schedule:1");
+        assert_eq!(MatchedRule::Device(2).to_string(), "device:2");
+        assert_eq!(MatchedRule::Geo(0).to_string(), "geo:0");
+        assert_eq!(MatchedRule::Default.to_string(), "default");
+    }
+
+    #[test]
+    fn test_validate_device_targets() {
+        assert!(validate_device_targets(&[device_target(Some(DeviceKind::Tablet), None, "https://example.com")]).is_ok());
+        assert!(validate_device_targets(&[device_target(None, None, "https://example.com")]).is_err());
+
+        let too_many = vec![device_target(None, Some(Platform::Ios), "https://example.com"); MAX_DEVICE_TARGETS + 1];
+        assert!(validate_device_targets(&too_many).is_err());
+    }
+
+    #[test]
+    fn test_normalize_geo_targets() {
+        let mut targets = vec![target(&["de", "At"], "https://example.de")];
+        normalize_geo_targets(&mut targets).unwrap();
+        assert_eq!(targets[0].countries, vec!["DE", "AT"]);
+
+        assert!(normalize_geo_targets(&mut [target(&[], "https://example.de")]).is_err());
+        assert!(normalize_geo_targets(&mut [target(&["DEU"], "https://example.de")]).is_err());
+        assert!(normalize_geo_targets(&mut [target(&["D1"], "https://example.de")]).is_err());
+
+        let mut too_many = vec![target(&["DE"], "https://example.de"); MAX_GEO_TARGETS + 1];
+        assert!(normalize_geo_targets(&mut too_many).is_err());
+    }
+}
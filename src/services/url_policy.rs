@@ -0,0 +1,215 @@
+use std::collections::HashSet;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use url::{Host, Url};
+
+/// Public shorteners that links may not chain through: the hop hides the final
+/// destination from our checks, and that destination can change at any time.
+const DEFAULT_SHORTENERS: &[&str] = &[
+    "bit.ly", "bitly.com", "buff.ly", "cutt.ly", "goo.gl", "is.gd", "lnkd.in", "ow.ly", "rb.gy", "rebrand.ly",
+    "s.id", "shorturl.at", "t.co", "t.ly", "tiny.cc", "tinyurl.com", "tr.im", "v.gd",
+];
+
+/// Name suffixes that only resolve inside a private network.
+const PRIVATE_SUFFIXES: &[&str] = &[".localhost", ".local", ".internal", ".home.arpa"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UrlRejection {
+    /// Loopback, private, link-local or otherwise non-public address or name.
+    PrivateAddress,
+    /// Points back at this service, so following it would loop.
+    SelfReference,
+    /// Goes through another link shortener.
+    NestedShortener,
+}
+
+impl UrlRejection {
+    /// Label for metrics.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PrivateAddress => "private_address",
+            Self::SelfReference => "self_reference",
+            Self::NestedShortener => "nested_shortener",
+        }
+    }
+}
+
+/// Decides which destinations links may redirect to, beyond being a
+/// well-formed http(s) URL. Names are not resolved: a public name that points
+/// at a private address is only caught by the network it is served from.
+pub struct UrlPolicy {
+    own_hosts: HashSet<String>,
+    shorteners: HashSet<String>,
+    allow_private: bool,
+}
+
+impl UrlPolicy {
+    /// `base_url` is the public address of this service; `allow_private`
+    /// permits private destinations, for local development.
+    pub fn new<S>(base_url: &str, extra_shorteners: S, allow_private: bool) -> Self
+    where
+        S: IntoIterator,
+        S::Item: AsRef<str>,
+    {
+        let own_hosts = Url::parse(base_url)
+            .ok()
+            .and_then(|url| url.host_str().map(normalize_host))
+            .into_iter()
+            .collect();
+
+        let shorteners = DEFAULT_SHORTENERS
+            .iter()
+            .map(|domain| domain.to_string())
+            .chain(extra_shorteners.into_iter().map(|domain| domain.as_ref().to_string()))
+            .map(|domain| normalize_host(domain.trim()))
+            .filter(|domain| !domain.is_empty())
+            .collect();
+
+        Self { own_hosts, shorteners, allow_private }
+    }
+
+    pub fn check(&self, url: &Url) -> Result<(), UrlRejection> {
+        let Some(host) = url.host() else {
+            return Err(UrlRejection::PrivateAddress);
+        };
+
+        let name = normalize_host(&host.to_string());
+        if self.own_hosts.contains(&name) {
+            return Err(UrlRejection::SelfReference);
+        }
+
+        let private = match host {
+            Host::Ipv4(ip) => is_private_v4(ip),
+            Host::Ipv6(ip) => is_private_v6(ip),
+            Host::Domain(domain) => is_private_name(domain),
+        };
+        if private && !self.allow_private {
+            return Err(UrlRejection::PrivateAddress);
+        }
+
+        let nested = self
+            .shorteners
+            .iter()
+            .any(|domain| name == *domain || name.strip_suffix(domain.as_str()).is_some_and(|rest| rest.ends_with('.')));
+        if nested {
+            return Err(UrlRejection::NestedShortener);
+        }
+
+        Ok(())
+    }
+}
+
+/// Lower-cased, without a trailing dot or leading `www.`, so spellings of the
+/// same host compare equal.
+fn normalize_host(host: &str) -> String {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    match host.strip_prefix("www.") {
+        Some(rest) => rest.to_string(),
+        None => host,
+    }
+}
+
+fn is_private_name(domain: &str) -> bool {
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    // Single-label names such as `intranet` only resolve through local search domains.
+    domain == "localhost" || !domain.contains('.') || PRIVATE_SUFFIXES.iter().any(|suffix| domain.ends_with(suffix))
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || first == 0
+        // Carrier-grade NAT, 100.64.0.0/10.
+        || (first == 100 && second & 0xc0 == 64)
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        return is_private_v4(ip);
+    }
+
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local, fc00::/7.
+        || first & 0xfe00 == 0xfc00
+        // Link-local, fe80::/10.
+        || first & 0xffc0 == 0xfe80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> UrlPolicy {
+        UrlPolicy::new("https://sho.rt", ["go.example.net"], false)
+    }
+
+    fn check(url: &str) -> Result<(), UrlRejection> {
+        policy().check(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_accepts_public_destinations() {
+        assert_eq!(check("https://example.com/page?q=1"), Ok(()));
+        assert_eq!(check("http://93.184.216.34/"), Ok(()));
+        assert_eq!(check("https://[2606:4700::1111]/"), Ok(()));
+        assert_eq!(check("https://notbit.ly/"), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_private_addresses() {
+        for url in [
+            "http://localhost:8080/admin",
+            "http://api.localhost/",
+            "http://printer.local/",
+            "http://intranet/",
+            "http://127.0.0.1/",
+            "http://2130706433/",
+            "http://0x7f.1/",
+            "http://10.1.2.3/",
+            "http://172.16.0.1/",
+            "http://192.168.1.1/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://100.64.0.1/",
+            "http://0.0.0.0/",
+            "http://[::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://[fd00::1]/",
+            "http://[fe80::1]/",
+            "http://metadata.google.internal/",
+        ] {
+            assert_eq!(check(url), Err(UrlRejection::PrivateAddress), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_allow_private_for_development() {
+        let policy = UrlPolicy::new("https://sho.rt", std::iter::empty::<&str>(), true);
+        assert_eq!(policy.check(&Url::parse("http://localhost:3000/").unwrap()), Ok(()));
+        assert_eq!(
+            policy.check(&Url::parse("https://sho.rt/abc").unwrap()),
+            Err(UrlRejection::SelfReference)
+        );
+    }
+
+    #[test]
+    fn test_rejects_self_references() {
+        assert_eq!(check("https://sho.rt/abc"), Err(UrlRejection::SelfReference));
+        assert_eq!(check("http://WWW.SHO.RT./abc"), Err(UrlRejection::SelfReference));
+        assert_eq!(check("https://api.sho.rt/"), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_nested_shorteners() {
+        assert_eq!(check("https://bit.ly/abc"), Err(UrlRejection::NestedShortener));
+        assert_eq!(check("https://www.tinyurl.com/abc"), Err(UrlRejection::NestedShortener));
+        assert_eq!(check("https://go.example.net/x"), Err(UrlRejection::NestedShortener));
+        assert_eq!(check("https://eu.go.example.net/x"), Err(UrlRejection::NestedShortener));
+    }
+}
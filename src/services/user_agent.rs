@@ -0,0 +1,88 @@
+use woothee::parser::Parser;
+
+use crate::models::link::{DeviceKind, Platform};
+
+/// Device class and operating system read from a `User-Agent` header. Either
+/// is `None` when the agent doesn't say, or is a crawler, console or library
+/// that no device rule is meant for.
+pub fn classify(user_agent: &str) -> (Option<DeviceKind>, Option<Platform>) {
+    let Some(result) = Parser::new().parse(user_agent) else {
+        return (None, None);
+    };
+
+    let platform = match result.os {
+        "iPhone" | "iPad" | "iPod" | "iOS" => Some(Platform::Ios),
+        "Android" => Some(Platform::Android),
+        "Mac OSX" => Some(Platform::Macos),
+        "Linux" => Some(Platform::Linux),
+        os if os.starts_with("Windows") && result.category == "pc" => Some(Platform::Windows),
+        _ => None,
+    };
+
+    // Android tablets are told apart from phones by the missing "Mobile" token.
+    // iPads on iPadOS 13+ send a desktop Safari agent and are seen as Macs.
+    let device = match result.category {
+        "pc" => Some(DeviceKind::Desktop),
+        "smartphone" | "mobilephone" => match platform {
+            Some(Platform::Ios) if result.os == "iPad" => Some(DeviceKind::Tablet),
+            Some(Platform::Android) if !user_agent.contains("Mobile") => Some(DeviceKind::Tablet),
+            _ => Some(DeviceKind::Mobile),
+        },
+        _ => None,
+    };
+
+    (device, platform)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_common_agents() {
+        let cases = [
+            (
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Mobile/15E148 Safari/604.1",
+                (Some(DeviceKind::Mobile), Some(Platform::Ios)),
+            ),
+            (
+                "Mozilla/5.0 (iPad; CPU OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.6 Mobile/15E148 Safari/604.1",
+                (Some(DeviceKind::Tablet), Some(Platform::Ios)),
+            ),
+            (
+                "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Mobile Safari/537.36",
+                (Some(DeviceKind::Mobile), Some(Platform::Android)),
+            ),
+            (
+                "Mozilla/5.0 (Linux; Android 13; SM-X200) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36",
+                (Some(DeviceKind::Tablet), Some(Platform::Android)),
+            ),
+            (
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36",
+                (Some(DeviceKind::Desktop), Some(Platform::Windows)),
+            ),
+            (
+                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Safari/605.1.15",
+                (Some(DeviceKind::Desktop), Some(Platform::Macos)),
+            ),
+            (
+                "Mozilla/5.0 (X11; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0",
+                (Some(DeviceKind::Desktop), Some(Platform::Linux)),
+            ),
+        ];
+
+        for (agent, expected) in cases {
+            assert_eq!(classify(agent), expected, "{}", agent);
+        }
+    }
+
+    #[test]
+    fn test_classify_unknown_and_crawlers() {
+        assert_eq!(classify(""), (None, None));
+        assert_eq!(classify("curl/8.5.0"), (None, None));
+        assert_eq!(
+            classify("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)").0,
+            None
+        );
+    }
+}